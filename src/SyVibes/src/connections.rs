@@ -0,0 +1,242 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+// Socket tables exposed by the kernel, with the protocol label shown in the UI
+const PROC_NET_TABLES: [(&str, &str); 4] = [
+    ("/proc/net/tcp", "TCP"),
+    ("/proc/net/tcp6", "TCP6"),
+    ("/proc/net/udp", "UDP"),
+    ("/proc/net/udp6", "UDP6"),
+];
+
+#[derive(Debug, Clone)]
+pub struct Connection {
+    pub proto: &'static str,
+    pub local: SocketAddr,
+    pub remote: SocketAddr,
+    pub state: &'static str,
+    pub inode: u64,
+    pub pid: Option<u32>,
+    pub process: Option<String>,
+}
+
+impl Connection {
+    /// True when the filter text appears in any displayed column (case-insensitive).
+    pub fn matches(&self, filter: &str) -> bool {
+        if filter.is_empty() {
+            return true;
+        }
+        let filter = filter.to_lowercase();
+        [
+            self.proto.to_lowercase(),
+            self.local.to_string(),
+            self.remote.to_string(),
+            self.state.to_lowercase(),
+            self.pid.map(|p| p.to_string()).unwrap_or_default(),
+            self.process.clone().unwrap_or_default().to_lowercase(),
+        ]
+        .iter()
+        .any(|field| field.contains(&filter))
+    }
+}
+
+/// Reads every socket table and resolves the owning process of each socket.
+pub fn list_connections() -> io::Result<Vec<Connection>> {
+    let mut connections = Vec::new();
+    let mut readable = false;
+
+    for (path, proto) in PROC_NET_TABLES {
+        // tcp6/udp6 are missing when IPv6 is disabled, so only fail if nothing was readable
+        if let Ok(contents) = fs::read_to_string(path) {
+            readable = true;
+            connections.extend(parse_proc_net(&contents, proto));
+        }
+    }
+
+    if !readable {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "no socket tables under /proc/net (connection listing is Linux only)",
+        ));
+    }
+
+    let owners = socket_owners();
+    let mut names: HashMap<u32, Option<String>> = HashMap::new();
+    for conn in &mut connections {
+        if let Some(&pid) = owners.get(&conn.inode) {
+            conn.pid = Some(pid);
+            conn.process = names.entry(pid).or_insert_with(|| process_name(pid)).clone();
+        }
+    }
+
+    connections.sort_by(|a, b| {
+        a.proto
+            .cmp(b.proto)
+            .then(a.local.port().cmp(&b.local.port()))
+            .then(a.remote.cmp(&b.remote))
+    });
+    Ok(connections)
+}
+
+/// Parses the body of a `/proc/net/{tcp,udp}{,6}` file, skipping the header line.
+pub fn parse_proc_net(contents: &str, proto: &'static str) -> Vec<Connection> {
+    let is_udp = proto.starts_with("UDP");
+
+    contents
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            // sl local_address rem_address st tx:rx tr:when retrnsmt uid timeout inode
+            if fields.len() < 10 {
+                return None;
+            }
+            let local = parse_socket_addr(fields[1])?;
+            let remote = parse_socket_addr(fields[2])?;
+            let state = u8::from_str_radix(fields[3], 16).ok()?;
+            let inode = fields[9].parse().ok()?;
+
+            Some(Connection {
+                proto,
+                local,
+                remote,
+                state: state_name(state, is_udp),
+                inode,
+                pid: None,
+                process: None,
+            })
+        })
+        .collect()
+}
+
+// Addresses are printed as the raw in-memory words of the network-order address,
+// so converting each word back with `to_ne_bytes` restores the original byte order.
+fn parse_socket_addr(field: &str) -> Option<SocketAddr> {
+    let (addr, port) = field.split_once(':')?;
+    let port = u16::from_str_radix(port, 16).ok()?;
+
+    let ip = match addr.len() {
+        8 => {
+            let word = u32::from_str_radix(addr, 16).ok()?;
+            IpAddr::V4(Ipv4Addr::from(word.to_ne_bytes()))
+        }
+        32 => {
+            let mut bytes = [0u8; 16];
+            for (i, chunk) in bytes.chunks_mut(4).enumerate() {
+                let word = u32::from_str_radix(&addr[i * 8..i * 8 + 8], 16).ok()?;
+                chunk.copy_from_slice(&word.to_ne_bytes());
+            }
+            IpAddr::V6(Ipv6Addr::from(bytes))
+        }
+        _ => return None,
+    };
+
+    Some(SocketAddr::new(ip, port))
+}
+
+fn state_name(state: u8, is_udp: bool) -> &'static str {
+    match state {
+        0x01 => "ESTABLISHED",
+        0x02 => "SYN_SENT",
+        0x03 => "SYN_RECV",
+        0x04 => "FIN_WAIT1",
+        0x05 => "FIN_WAIT2",
+        0x06 => "TIME_WAIT",
+        0x07 if is_udp => "UNCONN", // unconnected UDP sockets report TCP_CLOSE
+        0x07 => "CLOSE",
+        0x08 => "CLOSE_WAIT",
+        0x09 => "LAST_ACK",
+        0x0A => "LISTEN",
+        0x0B => "CLOSING",
+        0x0C => "NEW_SYN_RECV",
+        _ => "UNKNOWN",
+    }
+}
+
+/// Maps socket inodes to the PID holding them by scanning `/proc/<pid>/fd`.
+/// Processes we are not allowed to inspect are silently skipped.
+fn socket_owners() -> HashMap<u64, u32> {
+    let mut owners = HashMap::new();
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return owners;
+    };
+
+    for entry in entries.flatten() {
+        let Some(pid) = entry.file_name().to_str().and_then(|s| s.parse::<u32>().ok()) else {
+            continue;
+        };
+        let Ok(fds) = fs::read_dir(entry.path().join("fd")) else {
+            continue;
+        };
+
+        for fd in fds.flatten() {
+            if let Ok(target) = fs::read_link(fd.path()) {
+                let target = target.to_string_lossy();
+                if let Some(inode) = target
+                    .strip_prefix("socket:[")
+                    .and_then(|s| s.strip_suffix(']'))
+                    .and_then(|s| s.parse::<u64>().ok())
+                {
+                    owners.entry(inode).or_insert(pid);
+                }
+            }
+        }
+    }
+
+    owners
+}
+
+fn process_name(pid: u32) -> Option<String> {
+    fs::read_to_string(format!("/proc/{pid}/comm"))
+        .ok()
+        .map(|name| name.trim_end().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TCP: &str = "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 0100007F:0CEA 00000000:0000 0A 00000000:00000000 00:00000000 00000000   999        0 41873 1 0000000000000000 100 0 0 10 0
+   1: 0100007F:A1B2 0100007F:0CEA 01 00000000:00000000 00:00000000 00000000  1000        0 52011 1 0000000000000000 20 4 30 10 -1
+";
+
+    #[test]
+    fn parses_tcp_table() {
+        let conns = parse_proc_net(TCP, "TCP");
+        assert_eq!(conns.len(), 2);
+        assert_eq!(conns[0].local, "127.0.0.1:3306".parse().unwrap());
+        assert_eq!(conns[0].state, "LISTEN");
+        assert_eq!(conns[0].inode, 41873);
+        assert_eq!(conns[1].remote, "127.0.0.1:3306".parse().unwrap());
+        assert_eq!(conns[1].state, "ESTABLISHED");
+    }
+
+    #[test]
+    fn udp_close_is_unconnected() {
+        let udp = "header\n 0: 00000000:0044 00000000:0000 07 00000000:00000000 00:00000000 00000000 0 0 1234 2\n";
+        assert_eq!(parse_proc_net(udp, "UDP")[0].state, "UNCONN");
+    }
+
+    #[test]
+    fn parses_ipv6_loopback() {
+        let addr = parse_socket_addr("00000000000000000000000001000000:0050").unwrap();
+        assert_eq!(addr, "[::1]:80".parse().unwrap());
+    }
+
+    #[test]
+    fn filter_matches_any_column() {
+        let mut conn = parse_proc_net(TCP, "TCP").remove(0);
+        conn.pid = Some(4242);
+        conn.process = Some("MySQLd".into());
+
+        assert!(conn.matches(""));
+        assert!(conn.matches("listen"));
+        assert!(conn.matches(":3306"));
+        assert!(conn.matches("4242"));
+        assert!(conn.matches("mysql"));
+        assert!(!conn.matches("udp"));
+    }
+}
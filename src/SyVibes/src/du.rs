@@ -0,0 +1,322 @@
+use rayon::prelude::*;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::format::human_bytes;
+
+const LARGEST_FILES: usize = 50;
+const TREE_DEPTH: usize = 3;
+const TREE_CHILDREN: usize = 10;
+
+#[derive(Debug)]
+pub struct DirNode {
+    pub path: PathBuf,
+    pub size: u64,
+    pub files: u64,
+    pub children: Vec<DirNode>, // sorted by size, largest first
+}
+
+#[derive(Debug)]
+pub struct ScanResult {
+    pub root: DirNode,
+    pub largest_files: Vec<(PathBuf, u64)>,
+    pub errors: u64,
+}
+
+/// Shared state of a running scan, polled by the UI for progress and used to cancel it.
+#[derive(Debug, Default)]
+pub struct ScanProgress {
+    pub cancelled: AtomicBool,
+    pub files: AtomicU64,
+    pub bytes: AtomicU64,
+    pub errors: AtomicU64,
+}
+
+impl ScanProgress {
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Walks `root` in parallel without crossing into other file systems. Sizes are the
+/// space used on disk, so sparse files count what they occupy and a file with several
+/// hard links counts once. Returns `None` when the scan was cancelled through `progress`.
+pub fn scan(root: &Path, progress: Arc<ScanProgress>) -> Option<ScanResult> {
+    let root_dev = device_of(root);
+    let linked = Mutex::new(HashSet::new());
+    let (node, largest_files) = walk(root.to_path_buf(), root_dev, &progress, &linked);
+
+    if progress.is_cancelled() {
+        return None;
+    }
+
+    Some(ScanResult {
+        root: node,
+        largest_files,
+        errors: progress.errors.load(Ordering::Relaxed),
+    })
+}
+
+// `linked` holds the (device, inode) of every file with more than one link seen so far
+fn walk(
+    path: PathBuf,
+    root_dev: Option<u64>,
+    progress: &ScanProgress,
+    linked: &Mutex<HashSet<(u64, u64)>>,
+) -> (DirNode, Vec<(PathBuf, u64)>) {
+    let mut node = DirNode {
+        path,
+        size: 0,
+        files: 0,
+        children: Vec::new(),
+    };
+    let mut largest = Vec::new();
+
+    if progress.is_cancelled() {
+        return (node, largest);
+    }
+
+    let entries = match fs::read_dir(&node.path) {
+        Ok(entries) => entries,
+        Err(_) => {
+            progress.errors.fetch_add(1, Ordering::Relaxed);
+            return (node, largest);
+        }
+    };
+
+    let mut subdirs = Vec::new();
+    for entry in entries.flatten() {
+        // symlink_metadata so links are counted as themselves and never followed
+        let Ok(meta) = entry.path().symlink_metadata() else {
+            progress.errors.fetch_add(1, Ordering::Relaxed);
+            continue;
+        };
+
+        if meta.is_dir() {
+            if root_dev.is_none() || device_of(&entry.path()) == root_dev {
+                subdirs.push(entry.path());
+            }
+        } else {
+            let Some(size) = disk_size(&meta, linked) else {
+                continue; // another link to a file already counted
+            };
+            node.size += size;
+            node.files += 1;
+            largest.push((entry.path(), size));
+            progress.files.fetch_add(1, Ordering::Relaxed);
+            progress.bytes.fetch_add(size, Ordering::Relaxed);
+        }
+    }
+    keep_largest(&mut largest);
+
+    let results: Vec<_> = subdirs
+        .into_par_iter()
+        .map(|dir| walk(dir, root_dev, progress, linked))
+        .collect();
+
+    for (child, child_largest) in results {
+        node.size += child.size;
+        node.files += child.files;
+        node.children.push(child);
+        largest.extend(child_largest);
+    }
+    node.children.sort_by_key(|c| std::cmp::Reverse(c.size));
+    keep_largest(&mut largest);
+
+    (node, largest)
+}
+
+fn keep_largest(files: &mut Vec<(PathBuf, u64)>) {
+    files.sort_by_key(|f| std::cmp::Reverse(f.1));
+    files.truncate(LARGEST_FILES);
+}
+
+// Space the file takes, or `None` when one of its other hard links was counted already
+#[cfg(unix)]
+fn disk_size(meta: &fs::Metadata, linked: &Mutex<HashSet<(u64, u64)>>) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    if meta.nlink() > 1 && !linked.lock().unwrap().insert((meta.dev(), meta.ino())) {
+        return None;
+    }
+    Some(meta.blocks() * 512)
+}
+
+#[cfg(not(unix))]
+fn disk_size(meta: &fs::Metadata, _linked: &Mutex<HashSet<(u64, u64)>>) -> Option<u64> {
+    Some(meta.len())
+}
+
+#[cfg(unix)]
+fn device_of(path: &Path) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    path.symlink_metadata().ok().map(|m| m.dev())
+}
+
+#[cfg(not(unix))]
+fn device_of(_path: &Path) -> Option<u64> {
+    None
+}
+
+/// Renders the largest directories as an indented tree with their share of the total.
+pub fn render_tree(result: &ScanResult) -> String {
+    let mut out = String::new();
+    render_node(&result.root, result.root.size, 0, &mut out);
+    out
+}
+
+fn render_node(node: &DirNode, total: u64, depth: usize, out: &mut String) {
+    let percent = if total == 0 { 0.0 } else { node.size as f64 * 100.0 / total as f64 };
+    let name = if depth == 0 {
+        node.path.display().to_string()
+    } else {
+        node.path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| node.path.display().to_string())
+    };
+
+    out.push_str(&format!(
+        "{}{:>10}  {:>5.1}%  {}/  ({} files)\n",
+        "    ".repeat(depth),
+        human_bytes(node.size),
+        percent,
+        name,
+        node.files
+    ));
+
+    if depth < TREE_DEPTH {
+        for child in node.children.iter().take(TREE_CHILDREN) {
+            render_node(child, total, depth + 1, out);
+        }
+    }
+}
+
+pub fn render_largest_files(result: &ScanResult) -> String {
+    result
+        .largest_files
+        .iter()
+        .map(|(path, size)| format!("{:>10}  {}", human_bytes(*size), path.display()))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A fresh directory under the system temp dir, removed by the test when done
+    fn temp_tree(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("syvibes-du-{}-{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write(path: &Path, bytes: usize) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, vec![1u8; bytes]).unwrap();
+    }
+
+    fn on_disk(path: &Path) -> u64 {
+        disk_size(&path.symlink_metadata().unwrap(), &Mutex::new(HashSet::new())).unwrap()
+    }
+
+    fn node(path: &str, size: u64, children: Vec<DirNode>) -> DirNode {
+        DirNode {
+            path: PathBuf::from(path),
+            size,
+            files: 1,
+            children,
+        }
+    }
+
+    #[test]
+    fn directories_add_up_their_files() {
+        let root = temp_tree("sizes");
+        write(&root.join("top.bin"), 1000);
+        write(&root.join("big/one.bin"), 64 * 1024);
+        write(&root.join("big/deeper/two.bin"), 32 * 1024);
+        write(&root.join("small/three.bin"), 10);
+
+        let result = scan(&root, Arc::new(ScanProgress::default())).unwrap();
+        let expected = ["top.bin", "big/one.bin", "big/deeper/two.bin", "small/three.bin"]
+            .iter()
+            .map(|file| on_disk(&root.join(file)))
+            .sum::<u64>();
+        assert_eq!(result.root.size, expected);
+        assert_eq!(result.root.files, 4);
+        let big = &result.root.children[0];
+        assert!(big.path.ends_with("big"), "largest directory first");
+        assert_eq!(big.size, on_disk(&root.join("big/one.bin")) + on_disk(&root.join("big/deeper/two.bin")));
+        assert_eq!(big.files, 2);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn hard_links_count_once_and_sparse_files_by_their_blocks() {
+        let root = temp_tree("links");
+        write(&root.join("a/data.bin"), 64 * 1024);
+        fs::create_dir_all(root.join("b")).unwrap();
+        fs::hard_link(root.join("a/data.bin"), root.join("b/data.bin")).unwrap();
+        let sparse = fs::File::create(root.join("sparse.bin")).unwrap();
+        sparse.set_len(1 << 30).unwrap();
+
+        let result = scan(&root, Arc::new(ScanProgress::default())).unwrap();
+        assert_eq!(result.root.files, 2, "the second link is skipped");
+        assert!(result.root.size < 1 << 30, "a sparse file only counts its blocks");
+        assert_eq!(result.root.size, on_disk(&root.join("a/data.bin")) + on_disk(&root.join("sparse.bin")));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn keeps_the_largest_files_largest_first() {
+        let root = temp_tree("largest");
+        for i in 0..LARGEST_FILES + 5 {
+            write(&root.join(format!("d{}/f{}.bin", i % 4, i)), (i + 1) * 4096);
+        }
+
+        let result = scan(&root, Arc::new(ScanProgress::default())).unwrap();
+        assert_eq!(result.largest_files.len(), LARGEST_FILES);
+        assert!(result.largest_files.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+        let last = format!("f{}.bin", LARGEST_FILES + 4);
+        assert!(result.largest_files[0].0.ends_with(&last), "{:?}", result.largest_files[0]);
+        assert!(!result.largest_files.iter().any(|(path, _)| path.ends_with("f0.bin")));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn a_cancelled_scan_returns_nothing() {
+        let root = temp_tree("cancel");
+        write(&root.join("a/file.bin"), 10);
+        let progress = Arc::new(ScanProgress::default());
+        progress.cancel();
+        assert!(scan(&root, progress).is_none());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn tree_shows_each_share_of_the_total() {
+        let result = ScanResult {
+            root: node("/data", 4096, vec![node("/data/logs", 3072, vec![]), node("/data/tmp", 1024, vec![])]),
+            largest_files: Vec::new(),
+            errors: 0,
+        };
+        let rendered = render_tree(&result);
+        assert_eq!(
+            rendered.lines().collect::<Vec<_>>(),
+            [
+                "   4.0 KiB  100.0%  /data/  (1 files)",
+                "       3.0 KiB   75.0%  logs/  (1 files)",
+                "       1.0 KiB   25.0%  tmp/  (1 files)",
+            ]
+        );
+    }
+}
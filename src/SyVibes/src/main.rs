@@ -1,7 +1,35 @@
-use slint::SharedString;
+use slint::{ComponentHandle, ModelRc, SharedString, StandardListViewItem, VecModel};
+use std::collections::{BTreeMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
-use sysinfo::{Components, Disks, Networks, System, ProcessesToUpdate,ProcessRefreshKind};
+use std::time::Instant;
+use sysinfo::System;
+use tokio::sync::mpsc::error::TryRecvError;
+use tokio::task::JoinSet;
 use tokio::time::{sleep, Duration};
+use tokio_util::sync::CancellationToken;
+
+use source::{LiveSource, SharedSource};
+
+mod connections;
+mod du;
+mod format;
+mod meminfo;
+mod plugins;
+mod pressure;
+mod report;
+mod run;
+mod settings;
+mod snapshot;
+mod source;
+
+// Progress handle of the disk-usage scan currently running, if any
+type ScanSlot = Arc<Mutex<Option<Arc<du::ScanProgress>>>>;
+
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(2);
+const PSS_PROCESSES: usize = 15;
+const MAX_OOM_EVENTS: usize = 200;
 
 
 slint::include_modules!();  // This macro should define `MainWIndows`
@@ -9,224 +37,720 @@ slint::include_modules!();  // This macro should define `MainWIndows`
 
 #[tokio::main]
 async fn main() {
+// `syvibes run -- <command>` profiles a command from the terminal instead of opening the window
+let args: Vec<String> = std::env::args().collect();
+if args.get(1).map(String::as_str) == Some("run") {
+    std::process::exit(run::run_and_monitor(&args[2..]).await);
+}
+
 let ui = MainWindow::new().unwrap();
 let ui_handle = ui.as_weak();
 // ui.window().set_maximized(true);
-tokio::spawn(update_system_info(ui_handle.clone()));
-tokio::spawn(update_process_info(ui_handle.clone()));
-tokio::spawn(update_network_info(ui_handle.clone()));
-tokio::spawn(update_disk_info(ui_handle.clone()));
 
-ui.run().unwrap();
+let settings = Arc::new(Mutex::new(settings::Settings::load()));
+apply_appearance(&ui, &settings.lock().unwrap());
+let appearance_ui = ui_handle.clone();
+let appearance_settings = Arc::clone(&settings);
+ui.on_appearanceChanged(move || {
+    if let Some(ui) = appearance_ui.upgrade() {
+        let mut settings = appearance_settings.lock().unwrap();
+        store_appearance(&ui, &mut settings);
+        if let Err(e) = settings.save() {
+            eprintln!("SyVibes: cannot save settings: {}", e);
+        }
+    }
+});
+
+// Cancelled once the window is closed, every collector watches it between refreshes
+let shutdown = CancellationToken::new();
+let mut collectors = JoinSet::new();
+// Reading every smaps_rollup is costly, so per-process PSS/USS is opt-in
+let show_pss = Arc::new(AtomicBool::new(
+    settings.lock().unwrap().get("memory", "per_process_pss") == Some("true"),
+));
+ui.set_showPss(show_pss.load(Ordering::Relaxed));
+let pss_flag = Arc::clone(&show_pss);
+let pss_settings = Arc::clone(&settings);
+ui.on_pssToggled(move |enabled| {
+    pss_flag.store(enabled, Ordering::Relaxed);
+    let mut settings = pss_settings.lock().unwrap();
+    settings.set("memory", "per_process_pss", &enabled.to_string());
+    if let Err(e) = settings.save() {
+        eprintln!("SyVibes: cannot save settings: {}", e);
+    }
+});
+
+// One sysinfo backend shared by the collectors and the kill callback
+let source = LiveSource::shared();
+wire_kill_proc(&ui, Arc::clone(&source));
+collectors.spawn(update_system_info(ui_handle.clone(), shutdown.clone(), Arc::clone(&source), show_pss));
+collectors.spawn(update_process_info(ui_handle.clone(), shutdown.clone(), Arc::clone(&source)));
+collectors.spawn(update_network_info(ui_handle.clone(), shutdown.clone(), Arc::clone(&source)));
+collectors.spawn(update_disk_info(ui_handle.clone(), shutdown.clone(), source));
+collectors.spawn(update_connections_info(ui_handle.clone(), shutdown.clone()));
+
+// cgroups whose memory.events should be watched, e.g. `cgroups = /sys/fs/cgroup/system.slice/app.service`
+let oom_cgroups: Vec<PathBuf> = settings
+    .lock()
+    .unwrap()
+    .get("pressure", "cgroups")
+    .map(|list| list.split(',').map(str::trim).filter(|c| !c.is_empty()).map(PathBuf::from).collect())
+    .unwrap_or_default();
+collectors.spawn(update_pressure_info(ui_handle.clone(), shutdown.clone(), oom_cgroups));
+let plugin_specs = plugins::from_settings(&settings.lock().unwrap());
+collectors.spawn(update_custom_metrics(ui_handle.clone(), shutdown.clone(), plugin_specs));
+
+let scan_slot: ScanSlot = Arc::new(Mutex::new(None));
+let scan_ui = ui_handle.clone();
+let scan_start = Arc::clone(&scan_slot);
+ui.on_scanDisk(move |mount: SharedString| {
+    let progress = Arc::new(du::ScanProgress::default());
+    if let Some(previous) = scan_start.lock().unwrap().replace(Arc::clone(&progress)) {
+        previous.cancel();
+    }
+    tokio::spawn(run_disk_scan(
+        scan_ui.clone(),
+        PathBuf::from(mount.as_str()),
+        progress,
+        Arc::clone(&scan_start),
+    ));
+});
+let scan_cancel = Arc::clone(&scan_slot);
+ui.on_cancelScan(move || {
+    if let Some(progress) = scan_cancel.lock().unwrap().take() {
+        progress.cancel();
+    }
+});
+
+let snapshots = Arc::new(Mutex::new(snapshot::Snapshot::load_all()));
+ui.set_snapshotNames(snapshot_names(&snapshots.lock().unwrap()));
+let snapshot_ui = ui_handle.clone();
+let snapshot_store = Arc::clone(&snapshots);
+ui.on_takeSnapshot(move |name: SharedString| {
+    let name = match name.trim() {
+        "" => format!("snapshot {}", format::utc_time(std::time::SystemTime::now())),
+        name => name.to_string(),
+    };
+    tokio::spawn(take_snapshot(snapshot_ui.clone(), Arc::clone(&snapshot_store), name));
+});
+let diff_ui = ui_handle.clone();
+ui.on_diffSnapshots(move |from: SharedString, to: SharedString| {
+    let Some(ui) = diff_ui.upgrade() else {
+        return;
+    };
+    let snapshots = snapshots.lock().unwrap();
+    let find = |name: &str| snapshots.iter().find(|s| s.name == name);
+    let text = match (find(&from), find(&to)) {
+        (Some(before), Some(after)) => snapshot::render_diff(before, after, &snapshot::diff(before, after)),
+        _ => "Pick two snapshots to compare".to_string(),
+    };
+    ui.set_snapshotDiff(SharedString::from(text));
+});
+
+let run_result = ui.run();
+
+shutdown.cancel();
+if let Some(progress) = scan_slot.lock().unwrap().take() {
+    progress.cancel();
+}
+// Give collectors stuck in a refresh a moment to notice, then let the runtime drop them
+let drained = tokio::time::timeout(SHUTDOWN_GRACE, async {
+    while collectors.join_next().await.is_some() {}
+})
+.await;
+if drained.is_err() {
+    eprintln!("SyVibes: some collectors did not stop within {:?}", SHUTDOWN_GRACE);
+}
+
+if let Err(e) = run_result {
+    eprintln!("SyVibes: event loop failed: {}", e);
+    std::process::exit(1);
+}
 }
 
 
-async fn update_system_info(ui_handle: slint::Weak<MainWindow>) {
-    let mut sys = System::new_all();
+fn apply_appearance(ui: &MainWindow, settings: &settings::Settings) {
+    let mode = match settings.get("appearance", "theme") {
+        Some("light") => ThemeMode::Light,
+        Some("high-contrast") => ThemeMode::HighContrast,
+        _ => ThemeMode::Dark,
+    };
+    let monospace = settings.get("appearance", "monospace") == Some("true");
+    let theme = ui.global::<Theme>();
+    let font_scale = settings
+        .get("appearance", "font_scale")
+        .and_then(|v| v.parse::<f32>().ok())
+        .filter(|v| (theme.get_min_font_scale()..=theme.get_max_font_scale()).contains(v))
+        .unwrap_or(1.0);
+
+    ui.invoke_set_appearance(mode, monospace, font_scale);
+}
 
+fn store_appearance(ui: &MainWindow, settings: &mut settings::Settings) {
+    let theme = ui.global::<Theme>();
+    let mode = match theme.get_mode() {
+        ThemeMode::Light => "light",
+        ThemeMode::HighContrast => "high-contrast",
+        _ => "dark",
+    };
+
+    settings.set("appearance", "theme", mode);
+    settings.set("appearance", "monospace", &theme.get_monospace().to_string());
+    settings.set("appearance", "font_scale", &format!("{:.2}", theme.get_font_scale()));
+}
+
+
+async fn update_system_info(
+    ui_handle: slint::Weak<MainWindow>,
+    shutdown: CancellationToken,
+    source: SharedSource,
+    show_pss: Arc<AtomicBool>,
+) {
     loop {
-        sys.refresh_all();
-
-        let mem = format!(
-            "Total memory: \t{} bytes \nUsed memory: \t{} bytes \nTotal swap: \t{} bytes \nUsed swap: \t{} bytes",
-            sys.total_memory(),
-            sys.used_memory(),
-            sys.total_swap(),
-            sys.used_swap()
-        );
-
-        let cpu = sys
-            .cpus()
-            .iter()
-            .enumerate()
-            .map(|(i, cpu_info)| format!("CPU {} Usage: {}%", i, cpu_info.cpu_usage()))
-            .collect::<Vec<_>>()
-            .join("\n");
-
-        let system = format!(
-            "System Information:\n\
-            \tSystem name:           {:#?}\n\
-            \tKernel version:        {:#?}\n\
-            \tOS version:            {:#?}\n\
-            \tHost name:             {:#?}\n",
-            System::name(),
-            System::kernel_version(),
-            System::os_version(),
-            System::host_name()
-        );
-
-        let ui_handle_clone = ui_handle.clone();
-        slint::invoke_from_event_loop(move || {
-            if let Some(ui) = ui_handle_clone.upgrade() {
-                ui.set_memory(SharedString::from(mem));
-                ui.set_cpu(SharedString::from(cpu));
-                ui.set_system(SharedString::from(system));
+        let (mut mem, cpu, system) = {
+            let mut source = source.lock().unwrap();
+            source.refresh_system();
+            (
+                report::memory_text(&source.memory(), source.meminfo().as_ref()),
+                report::cpu_text(&source.cpu_usages()),
+                report::system_text(&source.system()),
+            )
+        };
+        if show_pss.load(Ordering::Relaxed) {
+            mem.push_str("\n\n");
+            // Reading every /proc/<pid>/smaps_rollup is blocking work, keep it off the runtime threads
+            let top = tokio::task::spawn_blocking(|| meminfo::top_processes_by_pss(PSS_PROCESSES))
+                .await
+                .unwrap_or_default();
+            mem.push_str(&report::process_memory_table(&top));
+        }
+
+        let delivered = update_ui(&ui_handle, move |ui| {
+            ui.set_memory(SharedString::from(mem));
+            ui.set_cpu(SharedString::from(cpu));
+            ui.set_system(SharedString::from(system));
+        });
+        if !delivered {
+            break;
+        }
+
+        tokio::select! {
+            _ = shutdown.cancelled() => break,
+            _ = sleep(Duration::from_secs(3)) => {}
+        }
+    }
+}
+
+async fn update_process_info(ui_handle: slint::Weak<MainWindow>, shutdown: CancellationToken, source: SharedSource) {
+    loop {
+        let procinfo = {
+            let mut source = source.lock().unwrap();
+            source.refresh_processes();
+            report::process_text(&source.processes())
+        };
+
+        let delivered = update_ui(&ui_handle, move |ui| {
+            ui.set_processInfo(SharedString::from(procinfo));
+        });
+        if !delivered {
+            break;
+        }
+
+        tokio::select! {
+            _ = shutdown.cancelled() => break,
+            _ = sleep(Duration::from_secs(3)) => {}
+        }
+    }
+}
+
+/// Kills every process whose name is typed into the Processes tab.
+fn wire_kill_proc(ui: &MainWindow, source: SharedSource) {
+    ui.on_killProc(move |name: SharedString| {
+        let name = name.trim();
+        if !name.is_empty() {
+            source.lock().unwrap().kill_by_name(name);
+        }
+    });
+}
+
+
+async fn update_network_info(ui_handle: slint::Weak<MainWindow>, shutdown: CancellationToken, source: SharedSource) {
+    let mut previous: Option<(Instant, Vec<source::NetworkSample>)> = None;
+
+    loop {
+        let current = {
+            let mut source = source.lock().unwrap();
+            source.refresh_networks();
+            source.networks()
+        };
+        let now = Instant::now();
+        let rates = match &previous {
+            Some((at, before)) => report::network_rates(before, &current, now - *at),
+            None => Default::default(),
+        };
+        let nets = report::network_text(&current, &rates);
+        previous = Some((now, current));
+
+        let delivered = update_ui(&ui_handle, move |ui| {
+            ui.set_network(SharedString::from(nets));
+        });
+        if !delivered {
+            break;
+        }
+
+        tokio::select! {
+            _ = shutdown.cancelled() => break,
+            _ = sleep(Duration::from_secs(3)) => {}
+        }
+    }
+}
+
+
+async fn update_connections_info(ui_handle: slint::Weak<MainWindow>, shutdown: CancellationToken) {
+    loop {
+        // Walking every /proc/<pid>/fd is blocking work, keep it off the runtime threads
+        let result = tokio::task::spawn_blocking(connections::list_connections)
+            .await
+            .unwrap_or_else(|e| Err(std::io::Error::other(e)));
+
+        let delivered = update_ui(&ui_handle, move |ui| {
+            match result {
+                Ok(conns) => {
+                    let filter = ui.get_connectionFilter();
+                    let rows: Vec<ModelRc<StandardListViewItem>> = conns
+                        .iter()
+                        .filter(|c| c.matches(filter.trim()))
+                        .map(|c| {
+                            let cells: Vec<StandardListViewItem> = vec![
+                                c.proto.into(),
+                                c.local.to_string().as_str().into(),
+                                c.remote.to_string().as_str().into(),
+                                c.state.into(),
+                                c.pid.map(|p| p.to_string()).unwrap_or_else(|| "-".into()).as_str().into(),
+                                c.process.as_deref().unwrap_or("-").into(),
+                            ];
+                            ModelRc::new(VecModel::from(cells))
+                        })
+                        .collect();
+
+                    ui.set_connectionStatus(SharedString::from(format!(
+                        "{} of {} sockets shown",
+                        rows.len(),
+                        conns.len()
+                    )));
+                    ui.set_connections(ModelRc::new(VecModel::from(rows)));
+                }
+                Err(e) => {
+                    ui.set_connectionStatus(SharedString::from(format!("Cannot list connections: {}", e)));
+                    ui.set_connections(ModelRc::default());
+                }
             }
-        })
-        .unwrap();
+        });
+        if !delivered {
+            break;
+        }
 
-        sleep(Duration::from_secs(3)).await;
+        tokio::select! {
+            _ = shutdown.cancelled() => break,
+            _ = sleep(Duration::from_secs(3)) => {}
+        }
     }
 }
 
-async fn update_process_info(ui_handle: slint::Weak<MainWindow>) {
-    let sys = Arc::new(Mutex::new(System::new_all()));
+
+async fn update_pressure_info(
+    ui_handle: slint::Weak<MainWindow>,
+    shutdown: CancellationToken,
+    cgroups: Vec<PathBuf>,
+) {
+    let (oom_tx, mut oom_rx) = tokio::sync::mpsc::unbounded_channel();
+    // The kernel log names the victim; without access to it we fall back to bare counters
+    let mut kmsg = pressure::spawn_kmsg_watcher(oom_tx, shutdown.clone());
+    let mut oom_events: VecDeque<pressure::OomEvent> = VecDeque::new();
+    let mut last_system_kills = pressure::read_oom_kill_count();
+    let mut last_cgroup_kills: Vec<Option<u64>> =
+        cgroups.iter().map(|c| pressure::read_cgroup_oom_kills(c)).collect();
 
     loop {
-        {
-            let mut sys_guard = sys.lock().unwrap(); // Stop the mutex before accessing sys
-            sys_guard.refresh_processes_specifics(
-                ProcessesToUpdate::All,
-                true,
-                ProcessRefreshKind::everything().without_cpu().without_disk_usage().without_environ(),
-            );
-        }
-
-        let sys_clone = Arc::clone(&sys);
-        let ui_handle_clone = ui_handle.clone();
-
-        slint::invoke_from_event_loop(move || {
-            if let Some(ui) = ui_handle_clone.upgrade() {
-                let sys_guard = sys_clone.lock().unwrap(); // Reloc before reading sys
-                let mut procinfo = String::new();
-
-                for (pid, process) in sys_guard.processes() {
-                    procinfo.push_str(&format!(
-                        "Process {:#?} (PID: {:#?})\nPath: {:?}\nMemory: {} KB\nCPU: {:.2}%\nStatus: {:?}\n",
-                        process.name(),
-                        pid,
-                        process.exe(),
-                        process.memory(),
-                        process.cpu_usage(),
-                        process.status()
-                    ));
-
-                    let disk_usage = process.disk_usage();
-                    procinfo.push_str(&format!(
-                        "Read bytes: new/total => {:?}/{:?}\nWritten bytes: new/total => {:?}/{:?}\n",
-                        disk_usage.read_bytes,
-                        disk_usage.total_read_bytes,
-                        disk_usage.written_bytes,
-                        disk_usage.total_written_bytes
-                    ));
+        let gauges: Vec<PressureGauge> = pressure::RESOURCES
+            .iter()
+            .filter_map(|resource| {
+                let p = pressure::read_pressure(resource)?;
+                let full = p.full.unwrap_or_default();
+                Some(PressureGauge {
+                    name: SharedString::from(*resource),
+                    some10: p.some[0],
+                    some60: p.some[1],
+                    some300: p.some[2],
+                    full10: full[0],
+                    full60: full[1],
+                    full300: full[2],
+                    has_full: p.full.is_some(),
+                })
+            })
+            .collect();
+
+        let load = System::load_average();
+        let load_avg = format!("Load average: {:.2} (1 min)  {:.2} (5 min)  {:.2} (15 min)", load.one, load.five, load.fifteen);
+
+        loop {
+            match oom_rx.try_recv() {
+                Ok(event) => oom_events.push_front(event),
+                // The watcher gave up on the kernel log, count kills from vmstat from now on
+                Err(TryRecvError::Disconnected) => {
+                    kmsg = false;
+                    break;
                 }
+                Err(TryRecvError::Empty) => break,
+            }
+        }
 
-                ui.set_processInfo(SharedString::from(procinfo));
-
-                let sys_clone = Arc::clone(&sys_clone);
-                ui.on_killProc(move |string: SharedString| {
-                    let  sys_guard = sys_clone.lock().unwrap();
-                    for (_, process) in sys_guard.processes() {
-                        if process.name() == string.as_str() {
-                            process.kill();
-                        }
-                    }
-                });
+        let system_kills = pressure::read_oom_kill_count();
+        if !kmsg {
+            if let (Some(now), Some(before)) = (system_kills, last_system_kills) {
+                if now > before {
+                    oom_events.push_front(pressure::OomEvent {
+                        at: std::time::SystemTime::now(),
+                        source: "system".into(),
+                        detail: format!("{} OOM kill(s), victim details need access to the kernel log", now - before),
+                    });
+                }
             }
-        })
-        .unwrap();
+        }
+        last_system_kills = system_kills.or(last_system_kills);
+
+        for (cgroup, last) in cgroups.iter().zip(last_cgroup_kills.iter_mut()) {
+            let now = pressure::read_cgroup_oom_kills(cgroup);
+            if let (Some(now), Some(before)) = (now, *last) {
+                if now > before {
+                    oom_events.push_front(pressure::OomEvent {
+                        at: std::time::SystemTime::now(),
+                        source: cgroup.display().to_string(),
+                        detail: format!("{} OOM kill(s) in cgroup", now - before),
+                    });
+                }
+            }
+            *last = now.or(*last);
+        }
+        oom_events.truncate(MAX_OOM_EVENTS);
+
+        let oom_text = if oom_events.is_empty() {
+            format!(
+                "No OOM kills since SyVibes started ({} since boot)",
+                system_kills.map_or("unknown".to_string(), |n| n.to_string())
+            )
+        } else {
+            oom_events
+                .iter()
+                .map(|e| format!("{} UTC  [{}]  {}", format::utc_time(e.at), e.source, e.detail))
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+        let status = if gauges.is_empty() {
+            "Pressure stall information is not available (needs Linux 4.20+ with CONFIG_PSI)".to_string()
+        } else {
+            String::new()
+        };
+
+        let delivered = update_ui(&ui_handle, move |ui| {
+            ui.set_loadAvg(SharedString::from(load_avg));
+            ui.set_pressure(ModelRc::new(VecModel::from(gauges)));
+            ui.set_pressureStatus(SharedString::from(status));
+            ui.set_oomEvents(SharedString::from(oom_text));
+        });
+        if !delivered {
+            break;
+        }
 
-        sleep(Duration::from_secs(3)).await;
+        tokio::select! {
+            _ = shutdown.cancelled() => break,
+            _ = sleep(Duration::from_secs(2)) => {}
+        }
     }
 }
 
 
+// Latest outcome of each plugin, keyed by plugin name
+type PluginOutcome = (std::time::SystemTime, Result<Vec<plugins::Metric>, String>);
+type PluginResults = Arc<Mutex<BTreeMap<String, PluginOutcome>>>;
+
+async fn update_custom_metrics(
+    ui_handle: slint::Weak<MainWindow>,
+    shutdown: CancellationToken,
+    specs: Vec<plugins::PluginSpec>,
+) {
+    if specs.is_empty() {
+        let hint = match settings::settings_path() {
+            Some(path) => format!("No plugins configured. Add a [plugin.<name>] section with a command to {}", path.display()),
+            None => "No plugins configured".to_string(),
+        };
+        update_ui(&ui_handle, move |ui| ui.set_customStatus(SharedString::from(hint)));
+        return;
+    }
 
-async fn update_network_info(ui_handle: slint::Weak<MainWindow>) {
-    let mut networks = Networks::new_with_refreshed_list();
+    let results: PluginResults = Arc::new(Mutex::new(Default::default()));
+    let mut runners = JoinSet::new();
+    for spec in specs {
+        runners.spawn(run_plugin(spec, Arc::clone(&results), ui_handle.clone(), shutdown.clone()));
+    }
+    while runners.join_next().await.is_some() {}
+}
 
+async fn run_plugin(
+    spec: plugins::PluginSpec,
+    results: PluginResults,
+    ui_handle: slint::Weak<MainWindow>,
+    shutdown: CancellationToken,
+) {
     loop {
-        let mut nets = String::new();
-        for (interface_name, data) in &networks {
-            nets.push_str(&format!(
-                "{}:\n\
-                 \tTotal Data:\n\
-                 \t\tReceived: {} B\n\
-                 \t\tTransmitted: {} B\n\
-                 \tTotal Packets:\n\
-                 \t\tReceived: {}\n\
-                 \t\tTransmitted: {}\n\
-                 \tErrors:\n\
-                 \t\tReceived: {}\n\
-                 \t\tTransmitted: {}\n\n",
-                interface_name,
-                data.total_received(),
-                data.total_transmitted(),
-                data.packets_received(),
-                data.packets_transmitted(),
-                data.errors_on_received(),
-                data.errors_on_transmitted()
-            ));
-        }
-
-        let ui_handle_clone = ui_handle.clone();
-        slint::invoke_from_event_loop(move || {
-            if let Some(ui) = ui_handle_clone.upgrade() {
-                ui.set_network(SharedString::from(nets.clone()));
-            }
-        })
-        .unwrap();
+        let outcome = tokio::select! {
+            _ = shutdown.cancelled() => break,
+            outcome = plugins::run(&spec) => outcome,
+        };
+
+        let (rows, status) = {
+            let mut results = results.lock().unwrap();
+            results.insert(spec.name.clone(), (std::time::SystemTime::now(), outcome));
+            custom_metric_rows(&results)
+        };
+
+        let delivered = update_ui(&ui_handle, move |ui| {
+            let rows: Vec<ModelRc<StandardListViewItem>> = rows
+                .iter()
+                .map(|row| {
+                    let cells: Vec<StandardListViewItem> = row.iter().map(|cell| cell.as_str().into()).collect();
+                    ModelRc::new(VecModel::from(cells))
+                })
+                .collect();
+            ui.set_customMetrics(ModelRc::new(VecModel::from(rows)));
+            ui.set_customStatus(SharedString::from(status));
+        });
+        if !delivered {
+            break;
+        }
 
-        sleep(Duration::from_secs(3)).await;
-        networks.refresh(true);
+        tokio::select! {
+            _ = shutdown.cancelled() => break,
+            _ = sleep(spec.interval) => {}
+        }
     }
 }
 
+// Plain strings so the rows can be sent to the UI thread, which builds the model
+fn custom_metric_rows(results: &BTreeMap<String, PluginOutcome>) -> (Vec<[String; 4]>, String) {
+    let mut rows = Vec::new();
+    let mut failing = 0;
+
+    for (plugin, (at, outcome)) in results {
+        let updated = format::utc_time(*at);
+        match outcome {
+            Ok(metrics) => rows.extend(
+                metrics
+                    .iter()
+                    .map(|m| [plugin.clone(), m.key.clone(), m.value.clone(), updated.clone()]),
+            ),
+            Err(e) => {
+                failing += 1;
+                rows.push([plugin.clone(), "(error)".into(), e.clone(), updated]);
+            }
+        }
+    }
+
+    let status = format!("{} plugin(s), {} failing", results.len(), failing);
+    (rows, status)
+}
 
-async fn update_disk_info(ui_handle: slint::Weak<MainWindow>) {
-    let mut disks = Disks::new_with_refreshed_list();
-    let mut components = Components::new_with_refreshed_list();
+
+async fn take_snapshot(
+    ui_handle: slint::Weak<MainWindow>,
+    snapshots: Arc<Mutex<Vec<snapshot::Snapshot>>>,
+    name: String,
+) {
+    let Ok(snap) = tokio::task::spawn_blocking(move || snapshot::Snapshot::capture(&name)).await else {
+        return;
+    };
+
+    let status = match snap.save() {
+        Ok(()) => format!("Saved snapshot \"{}\"", snap.name),
+        // Keeping it would show a snapshot that is gone after a restart
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+            let status = format!("Snapshot \"{}\" not taken, {}; pick another name", snap.name, e);
+            update_ui(&ui_handle, move |ui| ui.set_snapshotStatus(SharedString::from(status)));
+            return;
+        }
+        Err(e) => format!("Snapshot \"{}\" kept in memory only: {}", snap.name, e),
+    };
+    let names = {
+        let mut snapshots = snapshots.lock().unwrap();
+        // Taking a snapshot with an existing name replaces it
+        snapshots.retain(|s| s.name != snap.name);
+        snapshots.push(snap);
+        snapshots.iter().map(|s| s.name.clone()).collect::<Vec<_>>()
+    };
+
+    update_ui(&ui_handle, move |ui| {
+        let names: Vec<SharedString> = names.iter().map(SharedString::from).collect();
+        ui.set_snapshotNames(ModelRc::new(VecModel::from(names)));
+        ui.set_snapshotStatus(SharedString::from(status));
+    });
+}
+
+fn snapshot_names(snapshots: &[snapshot::Snapshot]) -> ModelRc<SharedString> {
+    let names: Vec<SharedString> = snapshots.iter().map(|s| SharedString::from(s.name.as_str())).collect();
+    ModelRc::new(VecModel::from(names))
+}
+
+
+async fn update_disk_info(ui_handle: slint::Weak<MainWindow>, shutdown: CancellationToken, source: SharedSource) {
+    let mut known_mounts: Vec<SharedString> = Vec::new();
 
     loop {
-        let mut disks_info = String::new();
-        let mut comp = String::new();
-
-        for disk in disks.iter() {
-            disks_info.push_str(&format!(
-                "Disk: {:#?}\n\
-                \tType: {:?}\n\
-                \tFile system: {:?}\n\
-                \tMount point: {:?}\n\
-                \tTotal space: {} B\n\
-                \tAvailable space: {} B\n\
-                \tUsed space: {} B\n\
-                \tIs removable: {}\n\
-                \tIs read-only: {}\n\n",
-                disk.name(),
-                disk.kind(),
-                disk.file_system(),
-                disk.mount_point(),
-                disk.total_space(),
-                disk.available_space(),
-                disk.total_space() - disk.available_space(),
-                disk.is_removable(),
-                disk.is_read_only()
-            ));
-        }
-
-        if components.is_empty() {
-            comp.push_str("No components found.\n");
-        }
-
-        for component in &components {
-            if let Some(temperature) = component.temperature() {
-                comp.push_str(&format!("{} \t{:#?}°C\n", component.label(), temperature));
-            } else {
-                comp.push_str(&format!("{} (unknown temperature)\n", component.label()));
+        let (disks, components) = {
+            let mut source = source.lock().unwrap();
+            source.refresh_disks();
+            (source.disks(), source.components())
+        };
+
+        // Only push the mount list when it changes, so the combo box keeps its selection
+        let mounts: Vec<SharedString> = disks
+            .iter()
+            .map(|disk| SharedString::from(disk.mount_point.to_string_lossy().as_ref()))
+            .collect();
+        let new_mounts = (mounts != known_mounts).then(|| mounts.clone());
+        known_mounts = mounts;
+
+        let disks_info = report::disk_text(&disks);
+        let comp = report::component_text(&components);
+
+        let delivered = update_ui(&ui_handle, move |ui| {
+            ui.set_disk(SharedString::from(disks_info));
+            ui.set_components(SharedString::from(comp));
+            if let Some(mounts) = new_mounts {
+                ui.set_mountPoints(ModelRc::new(VecModel::from(mounts)));
             }
+        });
+        if !delivered {
+            break;
         }
 
-        let ui_handle_clone = ui_handle.clone();
-        slint::invoke_from_event_loop(move || {
-            if let Some(ui) = ui_handle_clone.upgrade() {
-                ui.set_disk(SharedString::from(disks_info.clone()));
-                ui.set_components(SharedString::from(comp.clone()));
+        tokio::select! {
+            _ = shutdown.cancelled() => break,
+            _ = sleep(Duration::from_secs(4)) => {}
+        }
+    }
+}
+
+
+async fn run_disk_scan(
+    ui_handle: slint::Weak<MainWindow>,
+    root: PathBuf,
+    progress: Arc<du::ScanProgress>,
+    scan_slot: ScanSlot,
+) {
+    let mut worker = tokio::task::spawn_blocking({
+        let root = root.clone();
+        let progress = Arc::clone(&progress);
+        move || du::scan(&root, progress)
+    });
+
+    set_scan_state(&ui_handle, format!("Scanning {}...", root.display()), true);
+
+    let result = loop {
+        tokio::select! {
+            res = &mut worker => break res.ok().flatten(),
+            _ = sleep(Duration::from_millis(500)) => {
+                let status = format!(
+                    "Scanning {}: {} files, {} so far",
+                    root.display(),
+                    progress.files.load(Ordering::Relaxed),
+                    format::human_bytes(progress.bytes.load(Ordering::Relaxed))
+                );
+                // Nobody left to show the result to, stop walking the disk
+                if !progress.is_cancelled() && !set_scan_state(&ui_handle, status, true) {
+                    progress.cancel();
+                }
             }
-        })
-        .unwrap();
+        }
+    };
+
+    // A newer scan replaced this one, leave the UI to it
+    {
+        let mut slot = scan_slot.lock().unwrap();
+        match slot.as_ref() {
+            Some(current) if Arc::ptr_eq(current, &progress) => *slot = None,
+            Some(_) => return,
+            None => {}
+        }
+    }
+
+    let Some(result) = result else {
+        set_scan_state(&ui_handle, format!("Scan of {} cancelled", root.display()), false);
+        return;
+    };
+
+    let status = format!(
+        "{}: {} in {} files ({} unreadable entries)",
+        root.display(),
+        format::human_bytes(result.root.size),
+        result.root.files,
+        result.errors
+    );
+    let tree = du::render_tree(&result);
+    let files = du::render_largest_files(&result);
+
+    update_ui(&ui_handle, move |ui| {
+        ui.set_diskUsage(SharedString::from(tree));
+        ui.set_largestFiles(SharedString::from(files));
+        ui.set_scanStatus(SharedString::from(status));
+        ui.set_scanning(false);
+    });
+}
+
+fn set_scan_state(ui_handle: &slint::Weak<MainWindow>, status: String, scanning: bool) -> bool {
+    update_ui(ui_handle, move |ui| {
+        ui.set_scanStatus(SharedString::from(status));
+        ui.set_scanning(scanning);
+    })
+}
+
+/// Runs `update` on the UI thread. Returns false once the event loop has exited,
+/// which the collectors take as the signal to stop.
+fn update_ui(ui_handle: &slint::Weak<MainWindow>, update: impl FnOnce(MainWindow) + Send + 'static) -> bool {
+    ui_handle.upgrade_in_event_loop(update).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use source::fake::FakeSource;
+    use source::ProcessSample;
+
+    fn window_with_fake_source() -> (MainWindow, FakeSource) {
+        i_slint_backend_testing::init_no_event_loop();
+        let fake = FakeSource {
+            processes: vec![ProcessSample {
+                pid: 10,
+                name: "sleep".into(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let ui = MainWindow::new().unwrap();
+        wire_kill_proc(&ui, fake.clone().shared());
+        (ui, fake)
+    }
+
+    #[test]
+    fn kill_proc_reaches_the_source() {
+        let (ui, fake) = window_with_fake_source();
+        ui.invoke_killProc(" sleep ".into());
+        assert_eq!(*fake.killed.lock().unwrap(), vec!["sleep".to_string()]);
+    }
 
-        sleep(Duration::from_secs(4)).await;
-        disks.refresh(true);
-        components.refresh(false);
+    #[test]
+    fn kill_proc_ignores_blank_names() {
+        let (ui, fake) = window_with_fake_source();
+        ui.invoke_killProc("   ".into());
+        assert!(fake.killed.lock().unwrap().is_empty());
     }
 }
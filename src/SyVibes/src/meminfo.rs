@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+use std::fs;
+
+/// Fields of `/proc/meminfo` that matter to tell real pressure from page cache.
+/// Sizes are in bytes, huge page counts are in pages.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct MemInfo {
+    pub total: u64,
+    pub free: u64,
+    pub available: u64,
+    pub buffers: u64,
+    pub cached: u64,
+    pub swap_cached: u64,
+    pub shmem: u64,
+    pub slab: u64,
+    pub slab_reclaimable: u64,
+    pub slab_unreclaimable: u64,
+    pub swap_total: u64,
+    pub swap_free: u64,
+    pub huge_pages_total: u64,
+    pub huge_pages_free: u64,
+    pub huge_page_size: u64,
+}
+
+impl MemInfo {
+    pub fn read() -> Option<Self> {
+        fs::read_to_string("/proc/meminfo").ok().map(|text| Self::parse(&text))
+    }
+
+    pub fn parse(text: &str) -> Self {
+        let values = parse_kv_kib(text);
+        let get = |key: &str| values.get(key).copied().unwrap_or(0);
+
+        MemInfo {
+            total: get("MemTotal"),
+            free: get("MemFree"),
+            available: get("MemAvailable"),
+            buffers: get("Buffers"),
+            cached: get("Cached"),
+            swap_cached: get("SwapCached"),
+            shmem: get("Shmem"),
+            slab: get("Slab"),
+            slab_reclaimable: get("SReclaimable"),
+            slab_unreclaimable: get("SUnreclaim"),
+            swap_total: get("SwapTotal"),
+            swap_free: get("SwapFree"),
+            huge_pages_total: get("HugePages_Total"),
+            huge_pages_free: get("HugePages_Free"),
+            huge_page_size: get("Hugepagesize"),
+        }
+    }
+
+    /// Memory that cannot be reclaimed without swapping, i.e. what is really in use.
+    pub fn used(&self) -> u64 {
+        self.total.saturating_sub(self.available)
+    }
+}
+
+/// Proportional and unique set sizes of one process, from `/proc/<pid>/smaps_rollup`.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ProcessMemory {
+    pub pid: u32,
+    pub name: String,
+    pub rss: u64,
+    pub pss: u64,
+    pub uss: u64,
+    pub swap: u64,
+}
+
+impl ProcessMemory {
+    pub fn parse(pid: u32, name: String, smaps_rollup: &str) -> Self {
+        let values = parse_kv_kib(smaps_rollup);
+        let get = |key: &str| values.get(key).copied().unwrap_or(0);
+
+        ProcessMemory {
+            pid,
+            name,
+            rss: get("Rss"),
+            pss: get("Pss"),
+            uss: get("Private_Clean") + get("Private_Dirty"),
+            swap: get("Swap"),
+        }
+    }
+}
+
+/// Reads `smaps_rollup` of every process we may inspect and returns the `limit`
+/// largest by PSS. Kernel threads and other users' processes are skipped.
+pub fn top_processes_by_pss(limit: usize) -> Vec<ProcessMemory> {
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+
+    let mut processes: Vec<ProcessMemory> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let pid = entry.file_name().to_str()?.parse::<u32>().ok()?;
+            let rollup = fs::read_to_string(entry.path().join("smaps_rollup")).ok()?;
+            if rollup.is_empty() {
+                return None;
+            }
+            let name = fs::read_to_string(entry.path().join("comm"))
+                .map(|n| n.trim_end().to_string())
+                .unwrap_or_default();
+            Some(ProcessMemory::parse(pid, name, &rollup))
+        })
+        .collect();
+
+    processes.sort_by_key(|p| std::cmp::Reverse(p.pss));
+    processes.truncate(limit);
+    processes
+}
+
+// Parses `Key:   1234 kB` lines, converting kB values to bytes.
+// Values without a unit (huge page counts) are kept as they are.
+fn parse_kv_kib(text: &str) -> HashMap<&str, u64> {
+    text.lines()
+        .filter_map(|line| {
+            let (key, rest) = line.split_once(':')?;
+            let mut parts = rest.split_whitespace();
+            let value = parts.next()?.parse::<u64>().ok()?;
+            let value = match parts.next() {
+                Some("kB") => value * 1024,
+                _ => value,
+            };
+            Some((key.trim(), value))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_meminfo_in_bytes() {
+        let info = MemInfo::parse(
+            "MemTotal:        2048 kB\nMemFree:          512 kB\nMemAvailable:    1536 kB\n\
+             HugePages_Total:       4\nHugepagesize:    2048 kB\n",
+        );
+        assert_eq!(info.total, 2048 * 1024);
+        assert_eq!(info.used(), 512 * 1024);
+        assert_eq!(info.huge_pages_total, 4);
+        assert_eq!(info.huge_page_size, 2048 * 1024);
+        assert_eq!(info.shmem, 0);
+    }
+
+    #[test]
+    fn uss_is_private_memory() {
+        let rollup = "00400000-7fff rw-p 00000000 00:00 0  [rollup]\nRss: 100 kB\nPss: 60 kB\n\
+                      Private_Clean: 10 kB\nPrivate_Dirty: 30 kB\nSwap: 5 kB\n";
+        let p = ProcessMemory::parse(1, "init".into(), rollup);
+        assert_eq!(p.rss, 100 * 1024);
+        assert_eq!(p.pss, 60 * 1024);
+        assert_eq!(p.uss, 40 * 1024);
+        assert_eq!(p.swap, 5 * 1024);
+    }
+}
@@ -0,0 +1,210 @@
+use serde_json::Value;
+use std::time::Duration;
+use tokio::process::Command;
+
+use crate::settings::Settings;
+
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(10);
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    /// Decide from the output: JSON if it starts with `{`, key=value lines otherwise
+    Auto,
+    KeyValue,
+    Json,
+}
+
+/// An external command declared in the settings file:
+///
+/// ```text
+/// [plugin.queue]
+/// command = redis-cli llen jobs | sed 's/^/depth=/'
+/// interval = 5
+/// format = kv
+/// ```
+#[derive(Debug, Clone)]
+pub struct PluginSpec {
+    pub name: String,
+    pub command: String,
+    pub interval: Duration,
+    pub timeout: Duration,
+    pub format: OutputFormat,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Metric {
+    pub key: String,
+    pub value: String,
+}
+
+/// Collects every `[plugin.<name>]` section that has a `command`.
+pub fn from_settings(settings: &Settings) -> Vec<PluginSpec> {
+    settings
+        .sections()
+        .filter_map(|(section, values)| {
+            let name = section.strip_prefix("plugin.")?;
+            let command = values.get("command")?.clone();
+            let seconds = |key: &str, default: Duration| {
+                values
+                    .get(key)
+                    .and_then(|v| v.parse::<u64>().ok())
+                    .filter(|s| *s > 0)
+                    .map_or(default, Duration::from_secs)
+            };
+            let format = match values.get("format").map(String::as_str) {
+                Some("kv") => OutputFormat::KeyValue,
+                Some("json") => OutputFormat::Json,
+                _ => OutputFormat::Auto,
+            };
+
+            Some(PluginSpec {
+                name: name.to_string(),
+                command,
+                interval: seconds("interval", DEFAULT_INTERVAL),
+                timeout: seconds("timeout", DEFAULT_TIMEOUT),
+                format,
+            })
+        })
+        .collect()
+}
+
+/// Runs the plugin command through the platform shell and parses its stdout.
+pub async fn run(spec: &PluginSpec) -> Result<Vec<Metric>, String> {
+    let mut command = if cfg!(windows) {
+        let mut c = Command::new("cmd");
+        c.arg("/C").arg(&spec.command);
+        c
+    } else {
+        let mut c = Command::new("sh");
+        c.arg("-c").arg(&spec.command);
+        c
+    };
+    command.kill_on_drop(true);
+
+    let output = tokio::time::timeout(spec.timeout, command.output())
+        .await
+        .map_err(|_| format!("timed out after {}s", spec.timeout.as_secs()))?
+        .map_err(|e| format!("cannot run: {}", e))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("{}: {}", output.status, stderr.trim()));
+    }
+    parse_output(spec.format, &String::from_utf8_lossy(&output.stdout))
+}
+
+pub fn parse_output(format: OutputFormat, stdout: &str) -> Result<Vec<Metric>, String> {
+    let format = match format {
+        OutputFormat::Auto if stdout.trim_start().starts_with('{') => OutputFormat::Json,
+        OutputFormat::Auto => OutputFormat::KeyValue,
+        other => other,
+    };
+
+    match format {
+        OutputFormat::Json => {
+            let value: Value = serde_json::from_str(stdout).map_err(|e| format!("invalid JSON: {}", e))?;
+            if !value.is_object() {
+                return Err("JSON output must be an object".into());
+            }
+            let mut metrics = Vec::new();
+            flatten_json("", &value, &mut metrics);
+            Ok(metrics)
+        }
+        _ => Ok(stdout
+            .lines()
+            .filter_map(|line| {
+                let (key, value) = line.split_once('=')?;
+                let key = key.trim();
+                (!key.is_empty() && !key.starts_with('#')).then(|| Metric {
+                    key: key.to_string(),
+                    value: value.trim().to_string(),
+                })
+            })
+            .collect()),
+    }
+}
+
+// Nested objects become dotted keys: {"cache": {"hits": 3}} -> cache.hits=3
+fn flatten_json(prefix: &str, value: &Value, out: &mut Vec<Metric>) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                let key = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+                flatten_json(&key, value, out);
+            }
+        }
+        Value::String(s) => out.push(Metric {
+            key: prefix.to_string(),
+            value: s.clone(),
+        }),
+        Value::Null => {}
+        other => out.push(Metric {
+            key: prefix.to_string(),
+            value: other.to_string(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_plugin_sections() {
+        let settings = Settings::parse(
+            "[appearance]\ntheme = dark\n\n[plugin.queue]\ncommand = echo depth=3\ninterval = 5\nformat = kv\n\n\
+             [plugin.broken]\ninterval = 1\n",
+        );
+        let specs = from_settings(&settings);
+        assert_eq!(specs.len(), 1);
+        assert_eq!(specs[0].name, "queue");
+        assert_eq!(specs[0].interval, Duration::from_secs(5));
+        assert_eq!(specs[0].timeout, DEFAULT_TIMEOUT);
+        assert_eq!(specs[0].format, OutputFormat::KeyValue);
+    }
+
+    #[test]
+    fn auto_detects_json() {
+        let metrics = parse_output(OutputFormat::Auto, r#"{"cache": {"hits": 3, "name": "lru"}, "gone": null}"#).unwrap();
+        let pairs: Vec<(&str, &str)> = metrics.iter().map(|m| (m.key.as_str(), m.value.as_str())).collect();
+        assert_eq!(pairs, [("cache.hits", "3"), ("cache.name", "lru")]);
+    }
+
+    #[test]
+    fn key_value_lines_skip_comments() {
+        let metrics = parse_output(OutputFormat::Auto, "# header=1\ndepth = 3\nnot a metric\n").unwrap();
+        assert_eq!(
+            metrics,
+            [Metric {
+                key: "depth".into(),
+                value: "3".into()
+            }]
+        );
+    }
+
+    #[test]
+    fn json_must_be_an_object() {
+        assert!(parse_output(OutputFormat::Json, "[1, 2]").is_err());
+        assert!(parse_output(OutputFormat::Json, "depth=3").is_err());
+    }
+
+    #[tokio::test]
+    async fn runs_command_with_timeout() {
+        let spec = PluginSpec {
+            name: "t".into(),
+            command: "echo depth=3".into(),
+            interval: DEFAULT_INTERVAL,
+            timeout: Duration::from_secs(5),
+            format: OutputFormat::Auto,
+        };
+        assert_eq!(run(&spec).await.unwrap()[0].value, "3");
+
+        let slow = PluginSpec {
+            command: "sleep 5".into(),
+            timeout: Duration::from_millis(100),
+            ..spec
+        };
+        assert!(run(&slow).await.unwrap_err().starts_with("timed out"));
+    }
+}
@@ -0,0 +1,197 @@
+use std::fs;
+use std::path::Path;
+use std::time::SystemTime;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio_util::sync::CancellationToken;
+
+pub const RESOURCES: [&str; 3] = ["cpu", "memory", "io"];
+
+/// One `/proc/pressure/<resource>` file: share of wall time tasks were stalled,
+/// averaged over 10s, 60s and 300s. `full` is absent for the cpu resource on older kernels.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Pressure {
+    pub some: [f32; 3],
+    pub full: Option<[f32; 3]>,
+}
+
+#[derive(Debug, Clone)]
+pub struct OomEvent {
+    pub at: SystemTime,
+    pub source: String,
+    pub detail: String,
+}
+
+pub fn read_pressure(resource: &str) -> Option<Pressure> {
+    fs::read_to_string(format!("/proc/pressure/{}", resource))
+        .ok()
+        .and_then(|text| parse_pressure(&text))
+}
+
+pub fn parse_pressure(text: &str) -> Option<Pressure> {
+    let mut pressure = Pressure::default();
+    let mut found_some = false;
+
+    for line in text.lines() {
+        let mut parts = line.split_whitespace();
+        let kind = parts.next();
+        let mut averages = [0.0f32; 3];
+        for part in parts {
+            let Some((key, value)) = part.split_once('=') else {
+                continue;
+            };
+            let slot = match key {
+                "avg10" => 0,
+                "avg60" => 1,
+                "avg300" => 2,
+                _ => continue,
+            };
+            averages[slot] = value.parse().ok()?;
+        }
+
+        match kind {
+            Some("some") => {
+                pressure.some = averages;
+                found_some = true;
+            }
+            Some("full") => pressure.full = Some(averages),
+            _ => {}
+        }
+    }
+
+    found_some.then_some(pressure)
+}
+
+/// System-wide count of OOM kills since boot (`oom_kill` in `/proc/vmstat`).
+pub fn read_oom_kill_count() -> Option<u64> {
+    let text = fs::read_to_string("/proc/vmstat").ok()?;
+    find_counter(&text, "oom_kill")
+}
+
+/// `oom_kill` counter of a cgroup v2 `memory.events` file.
+pub fn read_cgroup_oom_kills(cgroup: &Path) -> Option<u64> {
+    let text = fs::read_to_string(cgroup.join("memory.events")).ok()?;
+    find_counter(&text, "oom_kill")
+}
+
+fn find_counter(text: &str, name: &str) -> Option<u64> {
+    text.lines().find_map(|line| {
+        let (key, value) = line.split_once(' ')?;
+        (key == name).then(|| value.trim().parse().ok()).flatten()
+    })
+}
+
+/// Extracts the victim from a kernel log record such as
+/// `3,1234,5678,-;Out of memory: Killed process 4321 (java) total-vm:...`.
+pub fn parse_kmsg_oom(record: &str) -> Option<String> {
+    let message = record.split_once(';').map_or(record, |(_, m)| m);
+    let start = message.find("Killed process ")?;
+    let victim = &message[start + "Killed process ".len()..];
+    // Keep "4321 (java)" and drop the memory counters that follow
+    let end = victim.find(')').map_or(victim.len(), |i| i + 1);
+
+    let scope = if message.contains("Memory cgroup out of memory") {
+        "cgroup limit"
+    } else {
+        "system"
+    };
+    Some(format!("killed process {} ({})", &victim[..end], scope))
+}
+
+/// Follows `/dev/kmsg` from its current end on a dedicated thread and forwards OOM kills
+/// until `shutdown` is cancelled. Returns false when the kernel log cannot be read (usually
+/// missing privileges). The thread drops `events` when it ends, for whatever reason.
+#[cfg(target_os = "linux")]
+pub fn spawn_kmsg_watcher(events: UnboundedSender<OomEvent>, shutdown: CancellationToken) -> bool {
+    use std::fs::OpenOptions;
+    use std::io::{BufRead, BufReader, ErrorKind, Seek, SeekFrom};
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::thread;
+    use std::time::Duration;
+
+    // How often the thread looks for new records, and so for a shutdown
+    const KMSG_POLL: Duration = Duration::from_millis(500);
+
+    let Ok(mut kmsg) = OpenOptions::new().read(true).custom_flags(libc::O_NONBLOCK).open("/dev/kmsg") else {
+        return false;
+    };
+    if kmsg.seek(SeekFrom::End(0)).is_err() {
+        return false;
+    }
+
+    // Each read on /dev/kmsg returns a single record; a blocking read could not see the shutdown,
+    // so the thread sleeps between reads once it has caught up
+    thread::spawn(move || {
+        let mut reader = BufReader::new(kmsg);
+        let mut record = Vec::new();
+        while !shutdown.is_cancelled() {
+            record.clear();
+            match reader.read_until(b'\n', &mut record) {
+                Ok(0) => break,
+                Ok(_) => {}
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                    thread::sleep(KMSG_POLL);
+                    continue;
+                }
+                // EPIPE means records were overwritten before we read them, keep going
+                Err(e) if e.raw_os_error() == Some(libc::EPIPE) => continue,
+                Err(_) => break,
+            }
+            // Drivers may log any bytes, which must not end the watcher
+            if let Some(detail) = parse_kmsg_oom(&String::from_utf8_lossy(&record)) {
+                let event = OomEvent {
+                    at: SystemTime::now(),
+                    source: "kernel log".into(),
+                    detail,
+                };
+                if events.send(event).is_err() {
+                    break;
+                }
+            }
+        }
+    });
+    true
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn spawn_kmsg_watcher(_events: UnboundedSender<OomEvent>, _shutdown: CancellationToken) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_some_and_full() {
+        let p = parse_pressure(
+            "some avg10=1.50 avg60=0.75 avg300=0.10 total=123\nfull avg10=0.50 avg60=0.00 avg300=0.00 total=45\n",
+        )
+        .unwrap();
+        assert_eq!(p.some, [1.5, 0.75, 0.1]);
+        assert_eq!(p.full, Some([0.5, 0.0, 0.0]));
+    }
+
+    #[test]
+    fn cpu_without_full_line() {
+        let p = parse_pressure("some avg10=0.00 avg60=0.00 avg300=0.00 total=0\n").unwrap();
+        assert_eq!(p.full, None);
+        assert_eq!(parse_pressure(""), None);
+    }
+
+    #[test]
+    fn finds_oom_counter() {
+        let vmstat = "pgfault 10\noom_kill 3\noom_kill_extra 9\n";
+        assert_eq!(find_counter(vmstat, "oom_kill"), Some(3));
+        assert_eq!(find_counter(vmstat, "missing"), None);
+    }
+
+    #[test]
+    fn kmsg_victim_and_scope() {
+        let record = "3,1234,5678,-;Memory cgroup out of memory: Killed process 4321 (java) total-vm:100kB";
+        assert_eq!(
+            parse_kmsg_oom(record).as_deref(),
+            Some("killed process 4321 (java) (cgroup limit)")
+        );
+        assert_eq!(parse_kmsg_oom("6,1,2,-;eth0: link up"), None);
+    }
+}
@@ -0,0 +1,361 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use crate::format::human_bytes;
+use crate::meminfo::{MemInfo, ProcessMemory};
+use crate::source::{ComponentSample, DiskSample, MemorySample, NetworkSample, ProcessSample, SystemDescription};
+
+/// Bytes per second received and transmitted on one interface.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct NetworkRate {
+    pub received: f64,
+    pub transmitted: f64,
+}
+
+pub fn memory_text(sample: &MemorySample, info: Option<&MemInfo>) -> String {
+    match info {
+        Some(info) => memory_breakdown(info),
+        None => format!(
+            "Total memory: \t{}\nUsed memory: \t{}\nTotal swap: \t{}\nUsed swap: \t{}",
+            human_bytes(sample.total),
+            human_bytes(sample.used),
+            human_bytes(sample.total_swap),
+            human_bytes(sample.used_swap)
+        ),
+    }
+}
+
+pub fn memory_breakdown(info: &MemInfo) -> String {
+    let b = human_bytes;
+    let mut out = format!(
+        "Total memory: \t{}\n\
+        Used memory: \t{} (total - available)\n\
+        Available: \t{}\n\
+        Free: \t\t{}\n\
+        Buffers: \t{}\n\
+        Cached: \t{}\n\
+        Shared (shmem): \t{}\n\
+        Slab: \t\t{} ({} reclaimable, {} unreclaimable)\n\
+        Total swap: \t{}\n\
+        Used swap: \t{}\n\
+        Swap cached: \t{}",
+        b(info.total),
+        b(info.used()),
+        b(info.available),
+        b(info.free),
+        b(info.buffers),
+        b(info.cached),
+        b(info.shmem),
+        b(info.slab),
+        b(info.slab_reclaimable),
+        b(info.slab_unreclaimable),
+        b(info.swap_total),
+        b(info.swap_total.saturating_sub(info.swap_free)),
+        b(info.swap_cached)
+    );
+
+    if info.huge_pages_total > 0 {
+        out.push_str(&format!(
+            "\nHuge pages: \t{} of {} free ({} each)",
+            info.huge_pages_free,
+            info.huge_pages_total,
+            b(info.huge_page_size)
+        ));
+    }
+    out
+}
+
+pub fn process_memory_table(processes: &[ProcessMemory]) -> String {
+    let mut out = format!("{:>8}  {:<16} {:>10} {:>10} {:>10} {:>10}\n", "PID", "Name", "PSS", "USS", "RSS", "Swap");
+    for p in processes {
+        out.push_str(&format!(
+            "{:>8}  {:<16} {:>10} {:>10} {:>10} {:>10}\n",
+            p.pid,
+            p.name,
+            human_bytes(p.pss),
+            human_bytes(p.uss),
+            human_bytes(p.rss),
+            human_bytes(p.swap)
+        ));
+    }
+    out
+}
+
+pub fn cpu_text(usages: &[f32]) -> String {
+    usages
+        .iter()
+        .enumerate()
+        .map(|(i, usage)| format!("CPU {} Usage: {}%", i, usage))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+pub fn system_text(system: &SystemDescription) -> String {
+    format!(
+        "System Information:\n\
+        \tSystem name:           {:#?}\n\
+        \tKernel version:        {:#?}\n\
+        \tOS version:            {:#?}\n\
+        \tHost name:             {:#?}\n",
+        system.name, system.kernel_version, system.os_version, system.host_name
+    )
+}
+
+/// One block per process, ordered by PID so the text does not reshuffle between refreshes.
+pub fn process_text(processes: &[ProcessSample]) -> String {
+    let mut sorted: Vec<&ProcessSample> = processes.iter().collect();
+    sorted.sort_by_key(|p| p.pid);
+
+    let mut out = String::new();
+    for p in sorted {
+        out.push_str(&format!(
+            "Process {:#?} (PID: {})\nPath: {:?}\nMemory: {} KB\nCPU: {:.2}%\nStatus: {}\n",
+            p.name,
+            p.pid,
+            p.exe,
+            p.memory / 1024,
+            p.cpu_usage,
+            p.status
+        ));
+        out.push_str(&format!(
+            "Read bytes: new/total => {}/{}\nWritten bytes: new/total => {}/{}\n",
+            p.read_bytes, p.total_read_bytes, p.written_bytes, p.total_written_bytes
+        ));
+    }
+    out
+}
+
+/// Per-second traffic between two samples. Interfaces that are new, or whose
+/// counters went backwards (driver reload), get no rate until the next sample.
+pub fn network_rates(
+    previous: &[NetworkSample],
+    current: &[NetworkSample],
+    elapsed: Duration,
+) -> BTreeMap<String, NetworkRate> {
+    let seconds = elapsed.as_secs_f64();
+    if seconds <= 0.0 {
+        return BTreeMap::new();
+    }
+
+    current
+        .iter()
+        .filter_map(|now| {
+            let before = previous.iter().find(|p| p.name == now.name)?;
+            let received = now.total_received.checked_sub(before.total_received)?;
+            let transmitted = now.total_transmitted.checked_sub(before.total_transmitted)?;
+            let rate = NetworkRate {
+                received: received as f64 / seconds,
+                transmitted: transmitted as f64 / seconds,
+            };
+            Some((now.name.clone(), rate))
+        })
+        .collect()
+}
+
+pub fn network_text(networks: &[NetworkSample], rates: &BTreeMap<String, NetworkRate>) -> String {
+    let mut sorted: Vec<&NetworkSample> = networks.iter().collect();
+    sorted.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut out = String::new();
+    for data in sorted {
+        let rate = |f: fn(&NetworkRate) -> f64| {
+            rates
+                .get(&data.name)
+                .map_or("-".to_string(), |r| format!("{}/s", human_bytes(f(r) as u64)))
+        };
+        out.push_str(&format!(
+            "{}:\n\
+             \tRate:\n\
+             \t\tReceived: {}\n\
+             \t\tTransmitted: {}\n\
+             \tTotal Data:\n\
+             \t\tReceived: {} B\n\
+             \t\tTransmitted: {} B\n\
+             \tTotal Packets:\n\
+             \t\tReceived: {}\n\
+             \t\tTransmitted: {}\n\
+             \tErrors:\n\
+             \t\tReceived: {}\n\
+             \t\tTransmitted: {}\n\n",
+            data.name,
+            rate(|r| r.received),
+            rate(|r| r.transmitted),
+            data.total_received,
+            data.total_transmitted,
+            data.packets_received,
+            data.packets_transmitted,
+            data.errors_on_received,
+            data.errors_on_transmitted
+        ));
+    }
+    out
+}
+
+pub fn disk_text(disks: &[DiskSample]) -> String {
+    let mut out = String::new();
+    for disk in disks {
+        out.push_str(&format!(
+            "Disk: {:#?}\n\
+            \tType: {}\n\
+            \tFile system: {:?}\n\
+            \tMount point: {:?}\n\
+            \tTotal space: {} B\n\
+            \tAvailable space: {} B\n\
+            \tUsed space: {} B\n\
+            \tIs removable: {}\n\
+            \tIs read-only: {}\n\n",
+            disk.name,
+            disk.kind,
+            disk.file_system,
+            disk.mount_point,
+            disk.total_space,
+            disk.available_space,
+            disk.total_space.saturating_sub(disk.available_space),
+            disk.is_removable,
+            disk.is_read_only
+        ));
+    }
+    out
+}
+
+pub fn component_text(components: &[ComponentSample]) -> String {
+    if components.is_empty() {
+        return "No components found.\n".to_string();
+    }
+
+    let mut out = String::new();
+    for component in components {
+        match component.temperature {
+            Some(temperature) => out.push_str(&format!("{} \t{:#?}°C\n", component.label, temperature)),
+            None => out.push_str(&format!("{} (unknown temperature)\n", component.label)),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn interface(name: &str, received: u64, transmitted: u64) -> NetworkSample {
+        NetworkSample {
+            name: name.into(),
+            total_received: received,
+            total_transmitted: transmitted,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn memory_falls_back_to_sysinfo_totals() {
+        let sample = MemorySample {
+            total: 8 << 30,
+            used: 2 << 30,
+            total_swap: 0,
+            used_swap: 0,
+        };
+        let text = memory_text(&sample, None);
+        assert!(text.contains("Total memory: \t8.0 GiB"));
+        assert!(text.contains("Used memory: \t2.0 GiB"));
+    }
+
+    #[test]
+    fn memory_breakdown_uses_available() {
+        let info = MemInfo {
+            total: 4 << 30,
+            available: 3 << 30,
+            ..Default::default()
+        };
+        let text = memory_text(&MemorySample::default(), Some(&info));
+        assert!(text.contains("Used memory: \t1.0 GiB (total - available)"));
+        assert!(!text.contains("Huge pages"));
+    }
+
+    #[test]
+    fn processes_are_sorted_by_pid() {
+        let processes = vec![
+            ProcessSample {
+                pid: 42,
+                name: "later".into(),
+                ..Default::default()
+            },
+            ProcessSample {
+                pid: 7,
+                name: "first".into(),
+                exe: Some(PathBuf::from("/bin/first")),
+                memory: 4096,
+                ..Default::default()
+            },
+        ];
+        let text = process_text(&processes);
+        let first = text.find("\"first\"").unwrap();
+        let later = text.find("\"later\"").unwrap();
+        assert!(first < later);
+        assert!(text.contains("(PID: 7)\nPath: Some(\"/bin/first\")\nMemory: 4 KB"));
+    }
+
+    #[test]
+    fn cpu_lines_are_numbered() {
+        assert_eq!(cpu_text(&[12.5, 0.0]), "CPU 0 Usage: 12.5%\nCPU 1 Usage: 0%");
+    }
+
+    #[test]
+    fn network_rates_per_second() {
+        let before = vec![interface("eth0", 1000, 500), interface("lo", 0, 0)];
+        let after = vec![interface("eth0", 4000, 1100), interface("wlan0", 10, 10)];
+        let rates = network_rates(&before, &after, Duration::from_secs(3));
+
+        assert_eq!(rates.len(), 1);
+        assert_eq!(
+            rates["eth0"],
+            NetworkRate {
+                received: 1000.0,
+                transmitted: 200.0
+            }
+        );
+    }
+
+    #[test]
+    fn network_rates_skip_counter_resets() {
+        let before = vec![interface("eth0", 5000, 5000)];
+        let after = vec![interface("eth0", 10, 6000)];
+        assert!(network_rates(&before, &after, Duration::from_secs(1)).is_empty());
+        assert!(network_rates(&before, &before, Duration::ZERO).is_empty());
+    }
+
+    #[test]
+    fn network_text_shows_missing_rate() {
+        let rates = BTreeMap::from([("eth0".to_string(), NetworkRate { received: 2048.0, transmitted: 0.0 })]);
+        let text = network_text(&[interface("lo", 0, 0), interface("eth0", 1, 2)], &rates);
+        assert!(text.starts_with("eth0:\n\tRate:\n\t\tReceived: 2.0 KiB/s\n\t\tTransmitted: 0 B/s"));
+        assert!(text.contains("lo:\n\tRate:\n\t\tReceived: -\n"));
+    }
+
+    #[test]
+    fn disk_used_space_never_underflows() {
+        let disk = DiskSample {
+            name: "sda1".into(),
+            total_space: 10,
+            available_space: 20,
+            ..Default::default()
+        };
+        assert!(disk_text(&[disk]).contains("Used space: 0 B"));
+    }
+
+    #[test]
+    fn components_without_temperature() {
+        assert_eq!(component_text(&[]), "No components found.\n");
+        let components = [
+            ComponentSample {
+                label: "cpu".into(),
+                temperature: Some(50.0),
+            },
+            ComponentSample {
+                label: "nvme".into(),
+                temperature: None,
+            },
+        ];
+        assert_eq!(component_text(&components), "cpu \t50.0°C\nnvme (unknown temperature)\n");
+    }
+}
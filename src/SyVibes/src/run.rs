@@ -0,0 +1,260 @@
+use std::collections::{HashMap, HashSet};
+use std::process::ExitStatus;
+use sysinfo::{Pid, ProcessRefreshKind, ProcessesToUpdate, System};
+use tokio::process::Command;
+use tokio::time::{interval, Duration, Instant, MissedTickBehavior};
+
+use crate::format::human_bytes;
+
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(250);
+const TOP_PROCESSES: usize = 10;
+
+// Last known numbers of one process of the tree, kept after it exits
+#[derive(Debug, Default)]
+struct Tracked {
+    name: String,
+    parent: Option<Pid>,
+    alive: bool,
+    peak_rss: u64,
+    cpu_seconds: f64,
+    read_bytes: u64,
+    written_bytes: u64,
+    // Raw counters at the previous sample, used to compute per-sample deltas
+    seen_read: u64,
+    seen_written: u64,
+}
+
+#[derive(Debug, Default)]
+struct Totals {
+    processes: HashMap<Pid, Tracked>,
+    peak_rss: u64,
+    peak_cpu: f32,
+    peak_concurrent: usize,
+}
+
+/// Entry point of `syvibes run -- <command> [args...]`.
+/// Returns the exit code SyVibes should exit with, which mirrors the child's.
+pub async fn run_and_monitor(args: &[String]) -> i32 {
+    let args = match args.first().map(String::as_str) {
+        Some("--") => &args[1..],
+        _ => args,
+    };
+    let Some((program, program_args)) = args.split_first() else {
+        eprintln!("usage: syvibes run -- <command> [args...]");
+        return 2;
+    };
+
+    let started = Instant::now();
+    let mut child = match Command::new(program).args(program_args).spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            eprintln!("syvibes: cannot run {}: {}", program, e);
+            return 127;
+        }
+    };
+    let Some(root) = child.id().map(Pid::from_u32) else {
+        eprintln!("syvibes: {} exited before it could be tracked", program);
+        return 1;
+    };
+
+    let mut sys = System::new();
+    let mut totals = Totals::default();
+    let mut ticker = interval(SAMPLE_INTERVAL);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut last_sample = Instant::now();
+
+    let status = loop {
+        tokio::select! {
+            status = child.wait() => break status,
+            _ = ticker.tick() => {
+                let elapsed = last_sample.elapsed().as_secs_f64();
+                last_sample = Instant::now();
+                sample(&mut sys, &[root], elapsed, &mut totals);
+            }
+        }
+    };
+    let wall = started.elapsed();
+
+    // One last sample for the time since the previous tick. The root is reaped by now and
+    // whatever it left running was reparented, so follow every process still tracked.
+    let mut roots: Vec<Pid> = totals.processes.iter().filter(|(_, t)| t.alive).map(|(pid, _)| *pid).collect();
+    roots.push(root);
+    sample(&mut sys, &roots, last_sample.elapsed().as_secs_f64(), &mut totals);
+
+    let status = match status {
+        Ok(status) => status,
+        Err(e) => {
+            eprintln!("syvibes: failed waiting for {}: {}", program, e);
+            return 1;
+        }
+    };
+
+    eprintln!("{}", report(&args.join(" "), status, wall, &totals));
+    exit_code(status)
+}
+
+fn sample(sys: &mut System, roots: &[Pid], elapsed: f64, totals: &mut Totals) {
+    sys.refresh_processes_specifics(
+        ProcessesToUpdate::All,
+        true,
+        ProcessRefreshKind::nothing().with_cpu().with_memory().with_disk_usage(),
+    );
+
+    let tree = descendants(sys, roots);
+
+    // When a child is reaped the kernel folds its I/O counters into the parent's,
+    // so that part of the parent's growth has already been counted for the child.
+    let mut reaped: HashMap<Pid, (u64, u64)> = HashMap::new();
+    for (pid, tracked) in totals.processes.iter_mut() {
+        if tracked.alive && !tree.contains(pid) {
+            tracked.alive = false;
+            if let Some(parent) = tracked.parent {
+                let absorbed = reaped.entry(parent).or_default();
+                absorbed.0 += tracked.seen_read;
+                absorbed.1 += tracked.seen_written;
+            }
+        }
+    }
+
+    let mut rss = 0;
+    let mut cpu = 0.0;
+
+    for pid in &tree {
+        let Some(process) = sys.process(*pid) else {
+            continue;
+        };
+        let disk_usage = process.disk_usage();
+        let (absorbed_read, absorbed_written) = reaped.get(pid).copied().unwrap_or_default();
+        let tracked = totals.processes.entry(*pid).or_default();
+
+        tracked.name = process.name().to_string_lossy().into_owned();
+        tracked.parent = process.parent();
+        tracked.alive = true;
+        tracked.peak_rss = tracked.peak_rss.max(process.memory());
+        tracked.cpu_seconds += process.cpu_usage() as f64 / 100.0 * elapsed;
+        tracked.read_bytes += disk_usage
+            .total_read_bytes
+            .saturating_sub(tracked.seen_read)
+            .saturating_sub(absorbed_read);
+        tracked.written_bytes += disk_usage
+            .total_written_bytes
+            .saturating_sub(tracked.seen_written)
+            .saturating_sub(absorbed_written);
+        tracked.seen_read = disk_usage.total_read_bytes;
+        tracked.seen_written = disk_usage.total_written_bytes;
+
+        rss += process.memory();
+        cpu += process.cpu_usage();
+    }
+
+    totals.peak_rss = totals.peak_rss.max(rss);
+    totals.peak_cpu = totals.peak_cpu.max(cpu);
+    totals.peak_concurrent = totals.peak_concurrent.max(tree.len());
+}
+
+/// Collects `roots` and every process below them in the parent/child hierarchy.
+fn descendants(sys: &System, roots: &[Pid]) -> Vec<Pid> {
+    let mut children: HashMap<Pid, Vec<Pid>> = HashMap::new();
+    for (pid, process) in sys.processes() {
+        if let Some(parent) = process.parent() {
+            children.entry(parent).or_default().push(*pid);
+        }
+    }
+
+    let mut seen = HashSet::new();
+    let mut stack = roots.to_vec();
+    let mut tree = Vec::new();
+    while let Some(pid) = stack.pop() {
+        if !seen.insert(pid) {
+            continue;
+        }
+        if sys.process(pid).is_some() {
+            tree.push(pid);
+        }
+        if let Some(kids) = children.get(&pid) {
+            stack.extend(kids);
+        }
+    }
+    tree
+}
+
+fn report(command: &str, status: ExitStatus, wall: Duration, totals: &Totals) -> String {
+    let cpu_seconds: f64 = totals.processes.values().map(|t| t.cpu_seconds).sum();
+    let read: u64 = totals.processes.values().map(|t| t.read_bytes).sum();
+    let written: u64 = totals.processes.values().map(|t| t.written_bytes).sum();
+    let average_cpu = if wall.as_secs_f64() > 0.0 {
+        cpu_seconds / wall.as_secs_f64() * 100.0
+    } else {
+        0.0
+    };
+
+    let mut out = format!(
+        "\n---- syvibes run summary ----\n\
+        Command:        {}\n\
+        Exit status:    {}\n\
+        Wall time:      {:.2} s\n\
+        Processes:      {} seen, {} at most at once\n\
+        Peak RSS:       {} (whole tree)\n\
+        CPU time:       {:.2} s sampled, {:.0}% average, {:.0}% peak\n\
+        Disk read:      {}\n\
+        Disk written:   {}\n",
+        command,
+        describe_status(status),
+        wall.as_secs_f64(),
+        totals.processes.len(),
+        totals.peak_concurrent,
+        human_bytes(totals.peak_rss),
+        cpu_seconds,
+        average_cpu,
+        totals.peak_cpu,
+        human_bytes(read),
+        human_bytes(written)
+    );
+
+    let mut top: Vec<(&Pid, &Tracked)> = totals.processes.iter().collect();
+    top.sort_by(|a, b| b.1.cpu_seconds.total_cmp(&a.1.cpu_seconds));
+    if !top.is_empty() {
+        out.push_str("Top processes by CPU:\n");
+    }
+    for (pid, tracked) in top.into_iter().take(TOP_PROCESSES) {
+        out.push_str(&format!(
+            "  {:>8}  {:<20} cpu {:>8.2} s  peak rss {:>10}  read {:>10}  written {:>10}\n",
+            pid.as_u32(),
+            tracked.name,
+            tracked.cpu_seconds,
+            human_bytes(tracked.peak_rss),
+            human_bytes(tracked.read_bytes),
+            human_bytes(tracked.written_bytes)
+        ));
+    }
+    out
+}
+
+fn describe_status(status: ExitStatus) -> String {
+    if let Some(code) = status.code() {
+        return format!("exited with code {}", code);
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return format!("killed by signal {}", signal);
+        }
+    }
+    status.to_string()
+}
+
+// Same convention as shells: 128 + signal number when the child was killed
+fn exit_code(status: ExitStatus) -> i32 {
+    if let Some(code) = status.code() {
+        return code;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+    1
+}
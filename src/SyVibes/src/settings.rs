@@ -0,0 +1,125 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// User settings stored as a small INI-like file:
+///
+/// ```text
+/// [appearance]
+/// theme = dark
+/// font_scale = 1.25
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct Settings {
+    sections: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl Settings {
+    /// Loads the settings file, falling back to defaults when it is missing or unreadable.
+    pub fn load() -> Self {
+        settings_path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|text| Self::parse(&text))
+            .unwrap_or_default()
+    }
+
+    pub fn parse(text: &str) -> Self {
+        let mut settings = Self::default();
+        let mut section = String::new();
+
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                section = name.trim().to_string();
+                continue;
+            }
+            if let Some((key, value)) = line.split_once('=') {
+                settings.set(&section, key.trim(), value.trim());
+            }
+        }
+        settings
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let path = settings_path()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no configuration directory"))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, self.to_string())
+    }
+
+    pub fn get(&self, section: &str, key: &str) -> Option<&str> {
+        self.sections.get(section)?.get(key).map(String::as_str)
+    }
+
+    pub fn sections(&self) -> impl Iterator<Item = (&str, &BTreeMap<String, String>)> {
+        self.sections.iter().map(|(name, values)| (name.as_str(), values))
+    }
+
+    pub fn set(&mut self, section: &str, key: &str, value: &str) {
+        self.sections
+            .entry(section.to_string())
+            .or_default()
+            .insert(key.to_string(), value.to_string());
+    }
+}
+
+impl std::fmt::Display for Settings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (name, values) in &self.sections {
+            if !name.is_empty() {
+                writeln!(f, "[{}]", name)?;
+            }
+            for (key, value) in values {
+                writeln!(f, "{} = {}", key, value)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// `$SYVIBES_CONFIG`, else `syvibes/settings.conf` in the platform config directory.
+pub fn settings_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("SYVIBES_CONFIG") {
+        return Some(PathBuf::from(path));
+    }
+
+    let base = if cfg!(windows) {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else {
+        env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+    };
+    base.map(|dir| dir.join("syvibes").join("settings.conf"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_ignores_comments_and_trims() {
+        let settings = Settings::parse("# comment\n; other\n[appearance]\n  theme =  light \nbroken line\n");
+        assert_eq!(settings.get("appearance", "theme"), Some("light"));
+        assert_eq!(settings.get("appearance", "broken line"), None);
+        assert_eq!(settings.get("memory", "theme"), None);
+    }
+
+    #[test]
+    fn round_trips_through_display() {
+        let mut settings = Settings::default();
+        settings.set("appearance", "font_scale", "1.25");
+        settings.set("memory", "per_process_pss", "true");
+        let reparsed = Settings::parse(&settings.to_string());
+        assert_eq!(reparsed.get("appearance", "font_scale"), Some("1.25"));
+        assert_eq!(reparsed.get("memory", "per_process_pss"), Some("true"));
+    }
+}
@@ -0,0 +1,334 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use sysinfo::{Disks, Networks, ProcessRefreshKind, ProcessesToUpdate, System};
+
+use crate::format::{human_bytes, utc_time};
+use crate::settings;
+
+const TOP_MEMORY_CHANGES: usize = 20;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ProcessEntry {
+    pub name: String,
+    pub memory: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DiskEntry {
+    pub total: u64,
+    pub available: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct NetworkEntry {
+    pub received: u64,
+    pub transmitted: u64,
+}
+
+/// State of the whole system at one point in time, saved as JSON under the config directory.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Snapshot {
+    pub name: String,
+    pub taken_at: u64, // seconds since the Unix epoch
+    pub processes: BTreeMap<u32, ProcessEntry>,
+    pub disks: BTreeMap<String, DiskEntry>,
+    pub networks: BTreeMap<String, NetworkEntry>,
+}
+
+impl Snapshot {
+    pub fn capture(name: &str) -> Self {
+        let mut sys = System::new();
+        sys.refresh_processes_specifics(ProcessesToUpdate::All, true, ProcessRefreshKind::nothing().with_memory());
+
+        let processes = sys
+            .processes()
+            .iter()
+            .map(|(pid, process)| {
+                let entry = ProcessEntry {
+                    name: process.name().to_string_lossy().into_owned(),
+                    memory: process.memory(),
+                };
+                (pid.as_u32(), entry)
+            })
+            .collect();
+
+        let disks = Disks::new_with_refreshed_list()
+            .iter()
+            .map(|disk| {
+                let entry = DiskEntry {
+                    total: disk.total_space(),
+                    available: disk.available_space(),
+                };
+                (disk.mount_point().to_string_lossy().into_owned(), entry)
+            })
+            .collect();
+
+        let networks = Networks::new_with_refreshed_list()
+            .iter()
+            .map(|(name, data)| {
+                let entry = NetworkEntry {
+                    received: data.total_received(),
+                    transmitted: data.total_transmitted(),
+                };
+                (name.clone(), entry)
+            })
+            .collect();
+
+        Snapshot {
+            name: name.to_string(),
+            taken_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            processes,
+            disks,
+            networks,
+        }
+    }
+
+    /// Writes the snapshot, replacing an earlier one of the same name. Fails with `AlreadyExists`
+    /// when a snapshot of another name has the same file, as "a b" and "a_b" would.
+    pub fn save(&self) -> io::Result<()> {
+        let dir = snapshot_dir().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no configuration directory"))?;
+        self.save_in(&dir)
+    }
+
+    fn save_in(&self, dir: &Path) -> io::Result<()> {
+        fs::create_dir_all(dir)?;
+        let path = dir.join(format!("{}.json", file_stem(&self.name)));
+        if let Some(other) = read(&path).filter(|other| other.name != self.name) {
+            let message = format!("its file name is taken by snapshot \"{}\"", other.name);
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, message));
+        }
+        let json = serde_json::to_string(self).map_err(io::Error::other)?;
+        fs::write(path, json)
+    }
+
+    /// Every saved snapshot, oldest first. Unreadable files are skipped.
+    pub fn load_all() -> Vec<Snapshot> {
+        let Some(entries) = snapshot_dir().and_then(|dir| fs::read_dir(dir).ok()) else {
+            return Vec::new();
+        };
+
+        let mut snapshots: Vec<Snapshot> = entries
+            .flatten()
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|entry| read(&entry.path()))
+            .collect();
+        snapshots.sort_by_key(|s| s.taken_at);
+        snapshots
+    }
+}
+
+fn read(path: &Path) -> Option<Snapshot> {
+    serde_json::from_str(&fs::read_to_string(path).ok()?).ok()
+}
+
+fn snapshot_dir() -> Option<PathBuf> {
+    settings::settings_path()?.parent().map(|dir| dir.join("snapshots"))
+}
+
+// Keep user-chosen names safe to use as file names
+fn file_stem(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect()
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct SnapshotDiff {
+    pub seconds: i64,
+    pub new_processes: Vec<(u32, ProcessEntry)>,
+    pub exited_processes: Vec<(u32, ProcessEntry)>,
+    /// (pid, name, memory before, memory after), largest change first
+    pub memory_changes: Vec<(u32, String, u64, u64)>,
+    /// (interface, bytes received, bytes transmitted); `None` when the counter went backwards
+    pub network: Vec<(String, Option<u64>, Option<u64>)>,
+    /// (mount point, bytes consumed), negative when space was freed
+    pub disks: Vec<(String, i64)>,
+}
+
+/// Compares two snapshots. A PID whose process name changed counts as exited and new,
+/// since the kernel reused it for a different program.
+pub fn diff(before: &Snapshot, after: &Snapshot) -> SnapshotDiff {
+    let mut result = SnapshotDiff {
+        seconds: after.taken_at as i64 - before.taken_at as i64,
+        ..Default::default()
+    };
+
+    for (pid, old) in &before.processes {
+        match after.processes.get(pid) {
+            Some(new) if new.name == old.name => {
+                if new.memory != old.memory {
+                    result.memory_changes.push((*pid, new.name.clone(), old.memory, new.memory));
+                }
+            }
+            _ => result.exited_processes.push((*pid, old.clone())),
+        }
+    }
+    for (pid, new) in &after.processes {
+        match before.processes.get(pid) {
+            Some(old) if old.name == new.name => {}
+            _ => result.new_processes.push((*pid, new.clone())),
+        }
+    }
+    result
+        .memory_changes
+        .sort_by_key(|(_, _, old, new)| std::cmp::Reverse(old.abs_diff(*new)));
+
+    for (name, new) in &after.networks {
+        if let Some(old) = before.networks.get(name) {
+            result.network.push((
+                name.clone(),
+                new.received.checked_sub(old.received),
+                new.transmitted.checked_sub(old.transmitted),
+            ));
+        }
+    }
+
+    for (mount, new) in &after.disks {
+        if let Some(old) = before.disks.get(mount) {
+            result.disks.push((mount.clone(), old.available as i64 - new.available as i64));
+        }
+    }
+
+    result
+}
+
+pub fn render_diff(before: &Snapshot, after: &Snapshot, diff: &SnapshotDiff) -> String {
+    let mut out = format!(
+        "{} ({} UTC) -> {} ({} UTC), {} s apart\n\n",
+        before.name,
+        utc_time(UNIX_EPOCH + std::time::Duration::from_secs(before.taken_at)),
+        after.name,
+        utc_time(UNIX_EPOCH + std::time::Duration::from_secs(after.taken_at)),
+        diff.seconds
+    );
+
+    out.push_str(&format!("New processes ({}):\n", diff.new_processes.len()));
+    for (pid, p) in &diff.new_processes {
+        out.push_str(&format!("  + {:>8}  {:<24} {}\n", pid, p.name, human_bytes(p.memory)));
+    }
+    out.push_str(&format!("\nExited processes ({}):\n", diff.exited_processes.len()));
+    for (pid, p) in &diff.exited_processes {
+        out.push_str(&format!("  - {:>8}  {:<24} {}\n", pid, p.name, human_bytes(p.memory)));
+    }
+
+    out.push_str("\nMemory changes (largest first):\n");
+    for (pid, name, old, new) in diff.memory_changes.iter().take(TOP_MEMORY_CHANGES) {
+        let sign = if new >= old { '+' } else { '-' };
+        out.push_str(&format!(
+            "    {:>8}  {:<24} {} -> {} ({}{})\n",
+            pid,
+            name,
+            human_bytes(*old),
+            human_bytes(*new),
+            sign,
+            human_bytes(old.abs_diff(*new))
+        ));
+    }
+
+    out.push_str("\nNetwork traffic:\n");
+    for (name, received, transmitted) in &diff.network {
+        let show = |v: &Option<u64>| v.map_or("counter reset".to_string(), human_bytes);
+        out.push_str(&format!("    {:<16} received {}, transmitted {}\n", name, show(received), show(transmitted)));
+    }
+
+    out.push_str("\nDisk space:\n");
+    for (mount, consumed) in &diff.disks {
+        let verb = if *consumed >= 0 { "consumed" } else { "freed" };
+        out.push_str(&format!("    {:<24} {} {}\n", mount, human_bytes(consumed.unsigned_abs()), verb));
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(taken_at: u64, processes: &[(u32, &str, u64)], received: u64, available: u64) -> Snapshot {
+        Snapshot {
+            name: format!("at {}", taken_at),
+            taken_at,
+            processes: processes
+                .iter()
+                .map(|(pid, name, memory)| {
+                    let entry = ProcessEntry {
+                        name: name.to_string(),
+                        memory: *memory,
+                    };
+                    (*pid, entry)
+                })
+                .collect(),
+            disks: BTreeMap::from([("/".to_string(), DiskEntry { total: 1000, available })]),
+            networks: BTreeMap::from([(
+                "eth0".to_string(),
+                NetworkEntry {
+                    received,
+                    transmitted: 0,
+                },
+            )]),
+        }
+    }
+
+    #[test]
+    fn reused_pid_counts_as_exited_and_new() {
+        let before = snapshot(100, &[(1, "init", 10), (2, "old", 5), (3, "gone", 1)], 0, 0);
+        let after = snapshot(160, &[(1, "init", 10), (2, "new", 7), (4, "fresh", 2)], 0, 0);
+        let d = diff(&before, &after);
+
+        assert_eq!(d.seconds, 60);
+        let pids = |v: &[(u32, ProcessEntry)]| v.iter().map(|(pid, _)| *pid).collect::<Vec<_>>();
+        assert_eq!(pids(&d.exited_processes), [2, 3]);
+        assert_eq!(pids(&d.new_processes), [2, 4]);
+        assert!(d.memory_changes.is_empty());
+    }
+
+    #[test]
+    fn memory_changes_largest_first() {
+        let before = snapshot(0, &[(1, "a", 100), (2, "b", 100), (3, "c", 100)], 0, 0);
+        let after = snapshot(1, &[(1, "a", 110), (2, "b", 10), (3, "c", 100)], 0, 0);
+        let d = diff(&before, &after);
+        let pids: Vec<u32> = d.memory_changes.iter().map(|c| c.0).collect();
+        assert_eq!(pids, [2, 1]);
+    }
+
+    #[test]
+    fn network_reset_and_disk_freed() {
+        let before = snapshot(0, &[], 500, 100);
+        let after = snapshot(1, &[], 20, 300);
+        let d = diff(&before, &after);
+        assert_eq!(d.network, [("eth0".to_string(), None, Some(0))]);
+        assert_eq!(d.disks, [("/".to_string(), -200)]);
+
+        let text = render_diff(&before, &after, &d);
+        assert!(text.contains("received counter reset"));
+        assert!(text.contains("200 B freed"));
+    }
+
+    #[test]
+    fn file_stem_replaces_separators() {
+        assert_eq!(file_stem("before deploy/2"), "before_deploy_2");
+    }
+
+    #[test]
+    fn names_sharing_a_file_are_refused() {
+        let dir = std::env::temp_dir().join(format!("syvibes-snapshots-{}", std::process::id()));
+        let mut first = snapshot(1, &[], 0, 0);
+        first.name = "a b".into();
+        let mut second = first.clone();
+        second.name = "a_b".into();
+
+        first.save_in(&dir).unwrap();
+        first.save_in(&dir).unwrap(); // same name, replaced
+        let error = second.save_in(&dir).unwrap_err();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::AlreadyExists);
+        assert!(error.to_string().contains("\"a b\""));
+    }
+}
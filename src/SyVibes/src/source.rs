@@ -0,0 +1,307 @@
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use sysinfo::{Components, Disks, Networks, ProcessRefreshKind, ProcessesToUpdate, System};
+
+use crate::meminfo::MemInfo;
+
+/// Data source shared by the collectors and the kill callback.
+pub type SharedSource = Arc<Mutex<Box<dyn DataSource>>>;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MemorySample {
+    pub total: u64,
+    pub used: u64,
+    pub total_swap: u64,
+    pub used_swap: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SystemDescription {
+    pub name: Option<String>,
+    pub kernel_version: Option<String>,
+    pub os_version: Option<String>,
+    pub host_name: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProcessSample {
+    pub pid: u32,
+    pub name: String,
+    pub exe: Option<PathBuf>,
+    pub memory: u64,
+    pub cpu_usage: f32,
+    pub status: String,
+    pub read_bytes: u64,
+    pub total_read_bytes: u64,
+    pub written_bytes: u64,
+    pub total_written_bytes: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NetworkSample {
+    pub name: String,
+    pub total_received: u64,
+    pub total_transmitted: u64,
+    pub packets_received: u64,
+    pub packets_transmitted: u64,
+    pub errors_on_received: u64,
+    pub errors_on_transmitted: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DiskSample {
+    pub name: String,
+    pub kind: String,
+    pub file_system: String,
+    pub mount_point: PathBuf,
+    pub total_space: u64,
+    pub available_space: u64,
+    pub is_removable: bool,
+    pub is_read_only: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ComponentSample {
+    pub label: String,
+    pub temperature: Option<f32>,
+}
+
+/// Everything the periodic collectors read from the machine. Each `refresh_*`
+/// updates one area, the getters return what the last refresh saw.
+pub trait DataSource: Send {
+    fn refresh_system(&mut self);
+    fn refresh_processes(&mut self);
+    fn refresh_networks(&mut self);
+    fn refresh_disks(&mut self);
+
+    fn memory(&self) -> MemorySample;
+    /// Detailed `/proc/meminfo` breakdown, when the platform has one.
+    fn meminfo(&self) -> Option<MemInfo>;
+    fn cpu_usages(&self) -> Vec<f32>;
+    fn system(&self) -> SystemDescription;
+    fn processes(&self) -> Vec<ProcessSample>;
+    fn networks(&self) -> Vec<NetworkSample>;
+    fn disks(&self) -> Vec<DiskSample>;
+    fn components(&self) -> Vec<ComponentSample>;
+
+    /// Kills every process with exactly this name, returns how many were signalled.
+    fn kill_by_name(&self, name: &str) -> usize;
+}
+
+/// The real machine, read through sysinfo.
+pub struct LiveSource {
+    sys: System,
+    networks: Networks,
+    disks: Disks,
+    components: Components,
+}
+
+impl LiveSource {
+    pub fn new() -> Self {
+        LiveSource {
+            sys: System::new_all(),
+            networks: Networks::new_with_refreshed_list(),
+            disks: Disks::new_with_refreshed_list(),
+            components: Components::new_with_refreshed_list(),
+        }
+    }
+
+    pub fn shared() -> SharedSource {
+        Arc::new(Mutex::new(Box::new(Self::new())))
+    }
+}
+
+impl DataSource for LiveSource {
+    fn refresh_system(&mut self) {
+        self.sys.refresh_memory();
+        self.sys.refresh_cpu_usage();
+    }
+
+    fn refresh_processes(&mut self) {
+        self.sys.refresh_processes_specifics(
+            ProcessesToUpdate::All,
+            true,
+            ProcessRefreshKind::everything().without_cpu().without_disk_usage().without_environ(),
+        );
+    }
+
+    fn refresh_networks(&mut self) {
+        self.networks.refresh(true);
+    }
+
+    fn refresh_disks(&mut self) {
+        self.disks.refresh(true);
+        self.components.refresh(false);
+    }
+
+    fn memory(&self) -> MemorySample {
+        MemorySample {
+            total: self.sys.total_memory(),
+            used: self.sys.used_memory(),
+            total_swap: self.sys.total_swap(),
+            used_swap: self.sys.used_swap(),
+        }
+    }
+
+    fn meminfo(&self) -> Option<MemInfo> {
+        MemInfo::read()
+    }
+
+    fn cpu_usages(&self) -> Vec<f32> {
+        self.sys.cpus().iter().map(|cpu| cpu.cpu_usage()).collect()
+    }
+
+    fn system(&self) -> SystemDescription {
+        SystemDescription {
+            name: System::name(),
+            kernel_version: System::kernel_version(),
+            os_version: System::os_version(),
+            host_name: System::host_name(),
+        }
+    }
+
+    fn processes(&self) -> Vec<ProcessSample> {
+        self.sys
+            .processes()
+            .iter()
+            .map(|(pid, process)| {
+                let disk_usage = process.disk_usage();
+                ProcessSample {
+                    pid: pid.as_u32(),
+                    name: process.name().to_string_lossy().into_owned(),
+                    exe: process.exe().map(PathBuf::from),
+                    memory: process.memory(),
+                    cpu_usage: process.cpu_usage(),
+                    status: process.status().to_string(),
+                    read_bytes: disk_usage.read_bytes,
+                    total_read_bytes: disk_usage.total_read_bytes,
+                    written_bytes: disk_usage.written_bytes,
+                    total_written_bytes: disk_usage.total_written_bytes,
+                }
+            })
+            .collect()
+    }
+
+    fn networks(&self) -> Vec<NetworkSample> {
+        self.networks
+            .iter()
+            .map(|(name, data)| NetworkSample {
+                name: name.clone(),
+                total_received: data.total_received(),
+                total_transmitted: data.total_transmitted(),
+                packets_received: data.packets_received(),
+                packets_transmitted: data.packets_transmitted(),
+                errors_on_received: data.errors_on_received(),
+                errors_on_transmitted: data.errors_on_transmitted(),
+            })
+            .collect()
+    }
+
+    fn disks(&self) -> Vec<DiskSample> {
+        self.disks
+            .iter()
+            .map(|disk| DiskSample {
+                name: disk.name().to_string_lossy().into_owned(),
+                kind: format!("{:?}", disk.kind()),
+                file_system: disk.file_system().to_string_lossy().into_owned(),
+                mount_point: disk.mount_point().to_path_buf(),
+                total_space: disk.total_space(),
+                available_space: disk.available_space(),
+                is_removable: disk.is_removable(),
+                is_read_only: disk.is_read_only(),
+            })
+            .collect()
+    }
+
+    fn components(&self) -> Vec<ComponentSample> {
+        self.components
+            .iter()
+            .map(|component| ComponentSample {
+                label: component.label().to_string(),
+                temperature: component.temperature(),
+            })
+            .collect()
+    }
+
+    fn kill_by_name(&self, name: &str) -> usize {
+        self.sys
+            .processes()
+            .values()
+            .filter(|process| process.name() == name)
+            .filter(|process| process.kill())
+            .count()
+    }
+}
+
+/// Deterministic in-memory source for headless tests.
+#[cfg(test)]
+pub mod fake {
+    use super::*;
+
+    #[derive(Debug, Default, Clone)]
+    pub struct FakeSource {
+        pub memory: MemorySample,
+        pub meminfo: Option<MemInfo>,
+        pub cpu_usages: Vec<f32>,
+        pub system: SystemDescription,
+        pub processes: Vec<ProcessSample>,
+        pub networks: Vec<NetworkSample>,
+        pub disks: Vec<DiskSample>,
+        pub components: Vec<ComponentSample>,
+        /// Names passed to `kill_by_name`, in call order
+        pub killed: Arc<Mutex<Vec<String>>>,
+        /// Counts calls to each refresh_* method
+        pub refreshes: Arc<Mutex<Vec<&'static str>>>,
+    }
+
+    impl FakeSource {
+        pub fn shared(self) -> SharedSource {
+            Arc::new(Mutex::new(Box::new(self)))
+        }
+    }
+
+    impl DataSource for FakeSource {
+        fn refresh_system(&mut self) {
+            self.refreshes.lock().unwrap().push("system");
+        }
+        fn refresh_processes(&mut self) {
+            self.refreshes.lock().unwrap().push("processes");
+        }
+        fn refresh_networks(&mut self) {
+            self.refreshes.lock().unwrap().push("networks");
+        }
+        fn refresh_disks(&mut self) {
+            self.refreshes.lock().unwrap().push("disks");
+        }
+
+        fn memory(&self) -> MemorySample {
+            self.memory.clone()
+        }
+        fn meminfo(&self) -> Option<MemInfo> {
+            self.meminfo.clone()
+        }
+        fn cpu_usages(&self) -> Vec<f32> {
+            self.cpu_usages.clone()
+        }
+        fn system(&self) -> SystemDescription {
+            self.system.clone()
+        }
+        fn processes(&self) -> Vec<ProcessSample> {
+            self.processes.clone()
+        }
+        fn networks(&self) -> Vec<NetworkSample> {
+            self.networks.clone()
+        }
+        fn disks(&self) -> Vec<DiskSample> {
+            self.disks.clone()
+        }
+        fn components(&self) -> Vec<ComponentSample> {
+            self.components.clone()
+        }
+
+        fn kill_by_name(&self, name: &str) -> usize {
+            self.killed.lock().unwrap().push(name.to_string());
+            self.processes.iter().filter(|p| p.name == name).count()
+        }
+    }
+}
@@ -0,0 +1,116 @@
+//! Sealing stored values when the database is encrypted.
+//!
+//! The key is derived from the passphrase with Argon2id. Its random salt and
+//! parameters sit in `meta` under `crypt`, next to a sealed check value that tells a
+//! wrong passphrase apart from a damaged record. Values are sealed with
+//! XChaCha20-Poly1305 as `<24 byte nonce> <ciphertext>`, a fresh random nonce each
+//! time, and with the tree name and key as associated data so a sealed value moved
+//! to another record fails to open.
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use bincode::config::standard;
+use bincode::{Decode, Encode};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use zeroize::Zeroizing;
+
+use crate::error::{Result, TodoError};
+
+const NONCE: usize = 24;
+const CHECK: &[u8] = b"todo";
+
+/// The key of an unlocked database. The cipher wipes it when dropped.
+pub(crate) struct Cipher(XChaCha20Poly1305);
+
+// What `meta` keeps under `crypt`
+#[derive(Encode, Decode)]
+struct Header {
+    salt: [u8; 16],
+    memory: u32, // KiB
+    passes: u32,
+    lanes: u32,
+    check: Vec<u8>,
+}
+
+impl Cipher {
+    /// A key from `passphrase` and a fresh salt, with the header to store for it.
+    pub fn create(passphrase: &str) -> Result<(Cipher, Vec<u8>)> {
+        let params = Params::DEFAULT;
+        let mut salt = [0; 16];
+        OsRng.fill_bytes(&mut salt);
+        let cipher = derive(passphrase, &salt, params.clone())?;
+        let header = Header {
+            salt,
+            memory: params.m_cost(),
+            passes: params.t_cost(),
+            lanes: params.p_cost(),
+            check: cipher.seal("meta", b"crypt", CHECK),
+        };
+        let bytes = bincode::encode_to_vec(&header, standard()).expect("encoding a header into memory");
+        Ok((cipher, bytes))
+    }
+
+    /// The key `header` was made with, if `passphrase` is the right one.
+    pub fn unlock(header: &[u8], passphrase: &str) -> Result<Cipher> {
+        let (header, _): (Header, usize) =
+            bincode::decode_from_slice(header, standard()).map_err(|_| TodoError::decode("unreadable key header"))?;
+        let params = Params::new(header.memory, header.passes, header.lanes, None)
+            .map_err(|e| TodoError::decode(format!("bad key parameters: {}", e)))?;
+        let cipher = derive(passphrase, &header.salt, params)?;
+        match cipher.open("meta", b"crypt", &header.check) {
+            Ok(check) if check == CHECK => Ok(cipher),
+            _ => Err(TodoError::Passphrase("wrong passphrase".into())),
+        }
+    }
+
+    fn seal(&self, tree: &str, key: &[u8], plain: &[u8]) -> Vec<u8> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let aad = associated(tree, key);
+        let sealed = self
+            .0
+            .encrypt(&nonce, Payload { msg: plain, aad: &aad })
+            .expect("sealing a value in memory");
+        [nonce.as_slice(), &sealed].concat()
+    }
+
+    fn open(&self, tree: &str, key: &[u8], sealed: &[u8]) -> Result<Vec<u8>> {
+        let broken = || TodoError::decode("sealed value failed to open, it is damaged or was moved");
+        if sealed.len() < NONCE {
+            return Err(broken());
+        }
+        let (nonce, sealed) = sealed.split_at(NONCE);
+        let aad = associated(tree, key);
+        self.0
+            .decrypt(XNonce::from_slice(nonce), Payload { msg: sealed, aad: &aad })
+            .map_err(|_| broken())
+    }
+}
+
+fn derive(passphrase: &str, salt: &[u8], params: Params) -> Result<Cipher> {
+    let mut key = Zeroizing::new([0; 32]);
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, &mut *key)
+        .map_err(|e| TodoError::Passphrase(format!("cannot derive a key: {}", e)))?;
+    Ok(Cipher(XChaCha20Poly1305::new(Key::from_slice(&*key))))
+}
+
+fn associated(tree: &str, key: &[u8]) -> Vec<u8> {
+    [tree.as_bytes(), &[0], key].concat()
+}
+
+/// `plain` as it is stored in `tree` under `key`: sealed with `cipher`, if any.
+pub(crate) fn seal(cipher: Option<&Cipher>, tree: &str, key: &[u8], plain: &[u8]) -> Vec<u8> {
+    match cipher {
+        Some(cipher) => cipher.seal(tree, key, plain),
+        None => plain.to_vec(),
+    }
+}
+
+/// The reverse of [`seal`].
+pub(crate) fn open(cipher: Option<&Cipher>, tree: &str, key: &[u8], stored: &[u8]) -> Result<Vec<u8>> {
+    match cipher {
+        Some(cipher) => cipher.open(tree, key, stored),
+        None => Ok(stored.to_vec()),
+    }
+}
@@ -0,0 +1,222 @@
+use chrono::{DateTime, Datelike, Days, Duration, Local, NaiveDate, NaiveTime, TimeZone, Weekday};
+
+// A due date without a time means "by the end of that day"
+pub(crate) const END_OF_DAY: (u32, u32) = (23, 59);
+
+/// Parses a due date relative to `now`:
+///
+/// - `today`, `tomorrow`, `fri`, `next fri`, `next week`, `in 3 days`, `in 2h`
+/// - ISO dates: `2025-07-10`, optionally followed by a time
+/// - times: `9am`, `9:30pm`, `14:00`, `noon`, `midnight`
+///
+/// A weekday means its next occurrence, today included; `next <weekday>` skips today.
+/// A time on its own means today, or tomorrow once that time has passed.
+pub fn parse_when(text: &str, now: DateTime<Local>) -> Result<DateTime<Local>, String> {
+    let invalid = || format!("cannot understand the date '{}'", text);
+    let lowered = text.trim().to_lowercase();
+    let words: Vec<&str> = lowered.split_whitespace().filter(|w| *w != "at").collect();
+    let today = now.date_naive();
+
+    let (day, rest): (Option<NaiveDate>, &[&str]) = match words.as_slice() {
+        [] => return Err("empty date".into()),
+        ["in", amount, unit] => {
+            let offset = parse_offset(amount, unit).ok_or_else(invalid)?;
+            return Ok(now + offset);
+        }
+        ["in", span] => {
+            let split = span.find(|c: char| !c.is_ascii_digit()).ok_or_else(invalid)?;
+            let offset = parse_offset(&span[..split], &span[split..]).ok_or_else(invalid)?;
+            return Ok(now + offset);
+        }
+        ["today", rest @ ..] | ["tonight", rest @ ..] => (Some(today), rest),
+        ["tomorrow", rest @ ..] | ["tmr", rest @ ..] => (Some(today + Days::new(1)), rest),
+        ["next", "week", rest @ ..] => (Some(today + Days::new(7)), rest),
+        ["next", name, rest @ ..] => {
+            let weekday = parse_weekday(name).ok_or_else(invalid)?;
+            (Some(next_weekday(today, weekday, false)), rest)
+        }
+        [first, rest @ ..] => {
+            if let Some(weekday) = parse_weekday(first) {
+                (Some(next_weekday(today, weekday, true)), rest)
+            } else if let Ok(date) = NaiveDate::parse_from_str(first, "%Y-%m-%d") {
+                (Some(date), rest)
+            } else {
+                (None, &words[..])
+            }
+        }
+    };
+
+    let time = match rest {
+        [] => None,
+        [time] => Some(parse_time(time).ok_or_else(invalid)?),
+        _ => return Err(invalid()),
+    };
+
+    let naive = match (day, time) {
+        (Some(day), Some(time)) => day.and_time(time),
+        (Some(day), None) => day.and_time(NaiveTime::from_hms_opt(END_OF_DAY.0, END_OF_DAY.1, 0).unwrap()),
+        (None, Some(time)) if today.and_time(time) > now.naive_local() => today.and_time(time),
+        (None, Some(time)) => (today + Days::new(1)).and_time(time),
+        (None, None) => return Err(invalid()),
+    };
+
+    // Times skipped by a DST change have no local representation
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .ok_or_else(|| format!("{} does not exist in the local time zone", naive))
+}
+
+/// Parses a day for a report range: `today`, `yesterday`, an ISO date, or a weekday,
+/// meaning its latest occurrence, today included.
+pub fn parse_day(text: &str, today: NaiveDate) -> Result<NaiveDate, String> {
+    match text.trim().to_lowercase().as_str() {
+        "today" => Ok(today),
+        "yesterday" => Ok(today - Days::new(1)),
+        word => {
+            if let Some(weekday) = parse_weekday(word) {
+                let back = (today.weekday().num_days_from_monday() + 7 - weekday.num_days_from_monday()) % 7;
+                return Ok(today - Days::new(back.into()));
+            }
+            NaiveDate::parse_from_str(word, "%Y-%m-%d").map_err(|_| format!("cannot understand the day '{}'", text))
+        }
+    }
+}
+
+/// The first moment of `day` in the local time zone.
+pub fn day_start(day: NaiveDate) -> DateTime<Local> {
+    // Where a DST change skips midnight, the day starts an hour later
+    let midnight = day.and_time(NaiveTime::MIN);
+    Local
+        .from_local_datetime(&midnight)
+        .earliest()
+        .or_else(|| Local.from_local_datetime(&(midnight + Duration::hours(1))).earliest())
+        .unwrap_or_default()
+}
+
+fn parse_offset(amount: &str, unit: &str) -> Option<Duration> {
+    let amount: i64 = amount.parse().ok()?;
+    match unit {
+        "m" | "min" | "mins" | "minute" | "minutes" => Some(Duration::minutes(amount)),
+        "h" | "hour" | "hours" => Some(Duration::hours(amount)),
+        "d" | "day" | "days" => Some(Duration::days(amount)),
+        "w" | "week" | "weeks" => Some(Duration::weeks(amount)),
+        _ => None,
+    }
+}
+
+pub(crate) fn parse_weekday(word: &str) -> Option<Weekday> {
+    match word {
+        "mon" | "monday" => Some(Weekday::Mon),
+        "tue" | "tues" | "tuesday" => Some(Weekday::Tue),
+        "wed" | "wednesday" => Some(Weekday::Wed),
+        "thu" | "thurs" | "thursday" => Some(Weekday::Thu),
+        "fri" | "friday" => Some(Weekday::Fri),
+        "sat" | "saturday" => Some(Weekday::Sat),
+        "sun" | "sunday" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+fn next_weekday(from: NaiveDate, weekday: Weekday, include_today: bool) -> NaiveDate {
+    let ahead = (weekday.num_days_from_monday() + 7 - from.weekday().num_days_from_monday()) % 7;
+    let ahead = if ahead == 0 && !include_today { 7 } else { ahead };
+    from + Days::new(ahead.into())
+}
+
+fn parse_time(word: &str) -> Option<NaiveTime> {
+    match word {
+        "noon" => return NaiveTime::from_hms_opt(12, 0, 0),
+        "midnight" => return NaiveTime::from_hms_opt(0, 0, 0),
+        _ => {}
+    }
+
+    let (clock, offset) = if let Some(clock) = word.strip_suffix("am") {
+        (clock, Some(0))
+    } else if let Some(clock) = word.strip_suffix("pm") {
+        (clock, Some(12))
+    } else {
+        (word, None)
+    };
+    let (hour, minute) = match clock.split_once(':') {
+        Some((h, m)) => (h.parse::<u32>().ok()?, m.parse::<u32>().ok()?),
+        None if offset.is_some() => (clock.parse::<u32>().ok()?, 0),
+        None => return None, // a bare number is too ambiguous
+    };
+
+    let hour = match offset {
+        Some(offset) if (1..=12).contains(&hour) => hour % 12 + offset,
+        Some(_) => return None,
+        None => hour,
+    };
+    NaiveTime::from_hms_opt(hour, minute, 0)
+}
+
+/// Short local rendering, e.g. `Fri 11 Jul 09:00`, with the year only when it differs from `now`.
+pub fn show(at: DateTime<Local>, now: DateTime<Local>) -> String {
+    if at.year() == now.year() {
+        at.format("%a %d %b %H:%M").to_string()
+    } else {
+        at.format("%a %d %b %Y %H:%M").to_string()
+    }
+}
+
+pub fn from_timestamp(seconds: i64) -> DateTime<Local> {
+    Local.timestamp_opt(seconds, 0).earliest().unwrap_or_default()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use chrono::NaiveDateTime;
+    use std::sync::Once;
+
+    /// Puts the test binary in Central European time, which skips 02:00 to 03:00 on the
+    /// last Sunday of March. Only the first call writes `TZ`; every later one waits for
+    /// it, so no test reads the local zone while it changes.
+    pub(crate) fn cet() {
+        static ZONE: Once = Once::new();
+        ZONE.call_once(|| std::env::set_var("TZ", "CET-1CEST,M3.5.0,M10.5.0/3"));
+    }
+
+    /// `YYYY-MM-DD HH:MM` in the zone set by [`cet`].
+    pub(crate) fn at(text: &str) -> DateTime<Local> {
+        cet();
+        let naive = NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M").unwrap();
+        Local.from_local_datetime(&naive).unwrap()
+    }
+
+    fn parse(text: &str, now: &str) -> Result<DateTime<Local>, String> {
+        parse_when(text, at(now))
+    }
+
+    #[test]
+    fn days_and_times() {
+        let now = "2026-03-10 14:00"; // a Tuesday
+        assert_eq!(parse("tomorrow 9am", now), Ok(at("2026-03-11 09:00")));
+        assert_eq!(parse("tomorrow at 9:30pm", now), Ok(at("2026-03-11 21:30")));
+        assert_eq!(parse("9am", now), Ok(at("2026-03-11 09:00")), "past times mean tomorrow");
+        assert_eq!(parse("2025-07-10", now), Ok(at("2025-07-10 23:59")));
+        assert_eq!(parse("2025-07-10 14:00", now), Ok(at("2025-07-10 14:00")));
+        assert!(parse("9", now).is_err());
+        assert!(parse("tomorrow 9am sharp", now).is_err());
+    }
+
+    #[test]
+    fn weekdays_and_offsets() {
+        assert_eq!(parse("fri", "2026-03-10 14:00"), Ok(at("2026-03-13 23:59")));
+        assert_eq!(parse("next fri", "2026-03-10 14:00"), Ok(at("2026-03-13 23:59")));
+        assert_eq!(parse("fri", "2026-03-13 08:00"), Ok(at("2026-03-13 23:59")));
+        assert_eq!(parse("next fri", "2026-03-13 08:00"), Ok(at("2026-03-20 23:59")));
+        assert_eq!(parse("in 3 days", "2026-03-10 14:00"), Ok(at("2026-03-13 14:00")));
+        assert_eq!(parse("in 2h", "2026-03-10 14:00"), Ok(at("2026-03-10 16:00")));
+    }
+
+    #[test]
+    fn times_skipped_by_dst_are_refused() {
+        let gap = parse("2026-03-29 2:30", "2026-03-20 12:00").unwrap_err();
+        assert!(gap.contains("does not exist"), "{}", gap);
+        assert_eq!(parse("2026-03-29 3:30", "2026-03-20 12:00"), Ok(at("2026-03-29 03:30")));
+        assert_eq!(day_start(at("2026-03-29 12:00").date_naive()), at("2026-03-29 00:00"));
+    }
+}
@@ -0,0 +1,109 @@
+use sled::transaction::TransactionError;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+#[derive(Debug)]
+pub enum TodoError {
+    Io(io::Error),
+    Sled(sled::Error),
+    /// Another process holds the database lock
+    Locked(PathBuf),
+    /// A stored record could not be decoded
+    Decode { id: Option<u64>, reason: String },
+    NotFound(u64),
+    InvalidInput(String),
+    /// Talking to the other end of a sync failed
+    Sync(String),
+    /// The database is encrypted and the passphrase is missing or wrong
+    Passphrase(String),
+}
+
+pub type Result<T> = std::result::Result<T, TodoError>;
+
+impl TodoError {
+    pub fn decode(reason: impl Into<String>) -> Self {
+        TodoError::Decode {
+            id: None,
+            reason: reason.into(),
+        }
+    }
+
+    /// Attaches the record ID to a decode error raised without one.
+    pub fn in_record(self, id: u64) -> Self {
+        match self {
+            TodoError::Decode { id: None, reason } => TodoError::Decode { id: Some(id), reason },
+            other => other,
+        }
+    }
+
+    /// Process exit status for the CLI, so scripts can tell failures apart.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            TodoError::InvalidInput(_) => 2,
+            TodoError::NotFound(_) => 3,
+            TodoError::Decode { .. } => 4,
+            TodoError::Locked(_) => 5,
+            TodoError::Sled(_) => 6,
+            TodoError::Io(_) => 7,
+            TodoError::Sync(_) => 8,
+            TodoError::Passphrase(_) => 9,
+        }
+    }
+}
+
+impl fmt::Display for TodoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TodoError::Io(e) => write!(f, "I/O error: {}", e),
+            TodoError::Sled(e) => write!(f, "database error: {}", e),
+            TodoError::Locked(path) => write!(
+                f,
+                "the database at {} is in use by another todo process, try again once it exits",
+                path.display()
+            ),
+            TodoError::Decode { id: Some(id), reason } => {
+                write!(f, "todo #{} is unreadable ({}), try `todo migrate` or remove it", id, reason)
+            }
+            TodoError::Decode { id: None, reason } => write!(f, "unreadable record: {}", reason),
+            TodoError::NotFound(id) => write!(f, "no todo #{}", id),
+            TodoError::InvalidInput(message) => f.write_str(message),
+            TodoError::Sync(message) => write!(f, "sync failed: {}", message),
+            TodoError::Passphrase(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for TodoError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TodoError::Io(e) => Some(e),
+            TodoError::Sled(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for TodoError {
+    fn from(e: io::Error) -> Self {
+        TodoError::Io(e)
+    }
+}
+
+impl From<sled::Error> for TodoError {
+    fn from(e: sled::Error) -> Self {
+        match e {
+            sled::Error::Io(e) => TodoError::Io(e),
+            other => TodoError::Sled(other),
+        }
+    }
+}
+
+impl From<TransactionError<TodoError>> for TodoError {
+    fn from(e: TransactionError<TodoError>) -> Self {
+        match e {
+            TransactionError::Abort(e) => e,
+            TransactionError::Storage(e) => e.into(),
+        }
+    }
+}
@@ -0,0 +1,171 @@
+//! The event log: every transaction that changes todos stores one event in the
+//! `events` tree, keyed by a big-endian sequence number, holding each changed todo
+//! as it was before and after. Only the latest [`EVENT_LIMIT`] events are kept: the
+//! transaction that logs an event also drops the one that falls off.
+//!
+//! Todos inside events use the record encoding from `schema`, so old events stay
+//! readable when `Todo` changes. The event itself is `<version> <bincode payload>`.
+
+use bincode::config::standard;
+use bincode::{Decode, Encode};
+
+use crate::error::{Result, TodoError};
+use crate::{schema, Todo};
+
+const VERSION: u8 = 1;
+
+/// How many events the log keeps: twice the undo depth, as each undo logs an event
+/// of its own, so undoing all the way down still finds every event it needs.
+pub const EVENT_LIMIT: u64 = 2000;
+
+/// Why an event happened.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+pub enum EventKind {
+    Change,
+    /// Reverted event `n`
+    Undo(u64),
+    /// Applied event `n` again after undoing it
+    Redo(u64),
+    /// Took in changes made on another device
+    Sync,
+}
+
+#[derive(Debug, Clone)]
+pub struct Event {
+    pub seq: u64,
+    pub at: i64, // Unix seconds
+    pub kind: EventKind,
+    pub changes: Vec<Change>,
+}
+
+/// One todo in an event; `before` is `None` for an add, `after` for a delete.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    pub id: u64,
+    pub before: Option<Todo>,
+    pub after: Option<Todo>,
+}
+
+impl Change {
+    /// `add`, `delete`, `done`, `reopen` or `edit`.
+    pub fn action(&self) -> &'static str {
+        match (&self.before, &self.after) {
+            (None, _) => "add",
+            (_, None) => "delete",
+            (Some(before), Some(after)) if !before.done && after.done => "done",
+            (Some(before), Some(after)) if before.done && !after.done => "reopen",
+            _ => "edit",
+        }
+    }
+}
+
+#[derive(Encode, Decode)]
+struct StoredEvent {
+    at: i64,
+    kind: EventKind,
+    changes: Vec<StoredChange>,
+}
+
+#[derive(Encode, Decode)]
+struct StoredChange {
+    id: u64,
+    before: Option<Vec<u8>>,
+    after: Option<Vec<u8>>,
+}
+
+pub(crate) fn encode(at: i64, kind: EventKind, changes: &[Change]) -> Vec<u8> {
+    let stored = StoredEvent {
+        at,
+        kind,
+        changes: changes
+            .iter()
+            .map(|change| StoredChange {
+                id: change.id,
+                before: change.before.as_ref().map(schema::encode),
+                after: change.after.as_ref().map(schema::encode),
+            })
+            .collect(),
+    };
+    let mut bytes = vec![VERSION];
+    bincode::encode_into_std_write(&stored, &mut bytes, standard()).expect("encoding an event into memory");
+    bytes
+}
+
+pub(crate) fn decode(seq: u64, bytes: &[u8]) -> Result<Event> {
+    let corrupt = || TodoError::decode(format!("event {} is unreadable", seq));
+    let payload = match bytes {
+        [VERSION, payload @ ..] => payload,
+        _ => return Err(corrupt()),
+    };
+    let (stored, _): (StoredEvent, usize) =
+        bincode::decode_from_slice(payload, standard()).map_err(|_| corrupt())?;
+    let todo = |bytes: Option<Vec<u8>>| bytes.map(|bytes| schema::decode(&bytes)).transpose();
+    let changes = stored
+        .changes
+        .into_iter()
+        .map(|change| {
+            Ok(Change {
+                id: change.id,
+                before: todo(change.before)?,
+                after: todo(change.after)?,
+            })
+        })
+        .collect::<Result<_>>()?;
+    Ok(Event {
+        seq,
+        at: stored.at,
+        kind: stored.kind,
+        changes,
+    })
+}
+
+/// Stacks of event numbers live in `meta` as packed big-endian u64s, newest last.
+pub(crate) fn push(stack: Option<&[u8]>, seq: u64, limit: usize) -> Vec<u8> {
+    let mut bytes = stack.unwrap_or_default().to_vec();
+    bytes.extend_from_slice(&seq.to_be_bytes());
+    let excess = bytes.len().saturating_sub(limit * 8);
+    bytes.split_off(excess)
+}
+
+/// The stack without its newest entry, and that entry.
+pub(crate) fn pop(stack: &[u8]) -> Option<(Vec<u8>, u64)> {
+    let split = stack.len().checked_sub(8)?;
+    let (rest, top) = stack.split_at(split);
+    Some((rest.to_vec(), u64::from_be_bytes(top.try_into().ok()?)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stacks_keep_the_newest_entries() {
+        let mut stack = Vec::new();
+        for seq in 1..=4 {
+            stack = push(Some(&stack), seq, 3);
+        }
+        let (stack, top) = pop(&stack).unwrap();
+        assert_eq!(top, 4);
+        let (stack, top) = pop(&stack).unwrap();
+        assert_eq!(top, 3);
+        let (stack, top) = pop(&stack).unwrap();
+        assert_eq!(top, 2);
+        assert_eq!(pop(&stack), None, "the oldest fell off");
+    }
+
+    #[test]
+    fn events_read_back() {
+        let before = Todo::new("water plants".into(), crate::Prior::Soft);
+        let after = Todo { done: true, ..before.clone() };
+        let changes = [
+            Change { id: 3, before: Some(before), after: Some(after) },
+            Change { id: 4, before: None, after: Some(Todo::new("repot".into(), crate::Prior::Soft)) },
+        ];
+        let event = decode(9, &encode(1_700_000_000, EventKind::Redo(5), &changes)).unwrap();
+        assert_eq!((event.seq, event.at, event.kind), (9, 1_700_000_000, EventKind::Redo(5)));
+        assert_eq!(event.changes, changes);
+        assert_eq!(event.changes[0].action(), "done");
+        assert_eq!(event.changes[1].action(), "add");
+        assert!(decode(9, &[VERSION + 1]).is_err());
+    }
+}
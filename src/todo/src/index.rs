@@ -0,0 +1,170 @@
+use sled::{Db, Tree};
+use std::collections::BTreeSet;
+
+use crate::crypto::{self, Cipher};
+use crate::error::Result;
+use crate::query::{Cmp, Query, Term};
+use crate::{search, Todo};
+
+// Bump when the index layout changes, the trees are rebuilt on the next open
+const INDEX_VERSION: u8 = 3;
+
+#[derive(Clone, Copy)]
+pub(crate) enum Index {
+    Tag,
+    Project,
+    Due,
+    Parent,
+    Word,
+}
+
+/// The record tree, the secondary indexes kept next to it, the event log, the sync
+/// records with their `Uid` lookup, the time log, and the `meta` tree holding
+/// counters. Index keys are `<value> 0x00 <id>` for tags and projects, `<due> <id>`
+/// for due dates and `<parent> <id>` for subtasks, with empty values, so a filtered
+/// listing only decodes the records it will show. `by_word` is laid out in
+/// [`search`](crate::search), `time` in [`timelog`](crate::timelog).
+pub(crate) struct Trees {
+    pub todos: Tree,
+    pub tags: Tree,
+    pub projects: Tree,
+    pub due: Tree,
+    pub children: Tree,
+    pub words: Tree,
+    pub events: Tree,
+    pub sync: Tree,
+    pub uids: Tree,
+    pub time: Tree,
+    pub meta: Tree,
+}
+
+impl Trees {
+    pub fn open(db: &Db, cipher: Option<&Cipher>) -> Result<Self> {
+        let trees = Trees {
+            todos: db.open_tree("todos")?,
+            tags: db.open_tree("by_tag")?,
+            projects: db.open_tree("by_project")?,
+            due: db.open_tree("by_due")?,
+            children: db.open_tree("by_parent")?,
+            words: db.open_tree("by_word")?,
+            events: db.open_tree("events")?,
+            sync: db.open_tree("sync")?,
+            uids: db.open_tree("sync_uids")?,
+            time: db.open_tree("time")?,
+            meta: db.open_tree("meta")?,
+        };
+
+        let version = trees.meta.get("index_version")?;
+        if version.as_deref() != Some(&[INDEX_VERSION][..]) {
+            trees.rebuild(cipher)?;
+            trees.meta.insert("index_version", &[INDEX_VERSION])?;
+        }
+        Ok(trees)
+    }
+
+    /// IDs that can match `query`: the intersection of its indexed terms, or `None`
+    /// when no term is indexed and every record has to be checked.
+    pub fn candidates(&self, query: &Query) -> Result<Option<BTreeSet<u64>>> {
+        let mut result: Option<BTreeSet<u64>> = None;
+
+        for term in &query.terms {
+            let ids = match term {
+                Term::Tag(tag) => prefix_ids(&self.tags, tag)?,
+                Term::Project(project) => prefix_ids(&self.projects, project)?,
+                Term::Due(cmp @ (Cmp::Lt | Cmp::Le), at) => {
+                    let end = sortable(*at + i64::from(*cmp == Cmp::Le));
+                    self.due
+                        .range(..end.to_vec())
+                        .keys()
+                        .map(|key| Ok(id_at_end(&key?)))
+                        .collect::<Result<_>>()?
+                }
+                Term::Due(cmp @ (Cmp::Gt | Cmp::Ge), at) => {
+                    let start = sortable(*at + i64::from(*cmp == Cmp::Gt));
+                    self.due
+                        .range(start.to_vec()..)
+                        .keys()
+                        .map(|key| Ok(id_at_end(&key?)))
+                        .collect::<Result<_>>()?
+                }
+                _ => continue,
+            };
+            result = Some(match result {
+                Some(so_far) => so_far.intersection(&ids).copied().collect(),
+                None => ids,
+            });
+        }
+        Ok(result)
+    }
+
+    /// IDs of the direct subtasks of `parent`.
+    pub fn children(&self, parent: u64) -> Result<Vec<u64>> {
+        self.children
+            .scan_prefix(parent.to_be_bytes())
+            .keys()
+            .map(|key| Ok(id_at_end(&key?)))
+            .collect()
+    }
+
+    /// Refills every index from the records. An encrypted store gets no word index.
+    pub fn rebuild(&self, cipher: Option<&Cipher>) -> Result<()> {
+        for tree in [&self.tags, &self.projects, &self.due, &self.children, &self.words] {
+            tree.clear()?;
+        }
+        for entry in self.todos.iter() {
+            let (key, value) = entry?;
+            let id = id_at_end(&key);
+            let todo = crypto::open(cipher, "todos", &key, &value)
+                .and_then(|plain| Todo::deserialize(&plain))
+                .map_err(|e| e.in_record(id))?;
+            for (index, key) in entries(id, &todo, cipher.is_none()) {
+                let tree = match index {
+                    Index::Tag => &self.tags,
+                    Index::Project => &self.projects,
+                    Index::Due => &self.due,
+                    Index::Parent => &self.children,
+                    Index::Word => &self.words,
+                };
+                tree.insert(key, &[])?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Every index key one record contributes, the words only when `words` is set.
+pub(crate) fn entries(id: u64, todo: &Todo, words: bool) -> Vec<(Index, Vec<u8>)> {
+    let tags = todo.tags.iter().map(|tag| (Index::Tag, value_key(tag, id)));
+    let project = todo.project.as_deref().map(|p| (Index::Project, value_key(p, id)));
+    let due = todo.due.map(|due| (Index::Due, [sortable(due), id.to_be_bytes()].concat()));
+    let parent = todo.parent.map(|p| (Index::Parent, [p.to_be_bytes(), id.to_be_bytes()].concat()));
+    let words = words.then(|| search::entries(id, todo)).into_iter().flatten().map(|key| (Index::Word, key));
+    tags.chain(project).chain(due).chain(parent).chain(words).collect()
+}
+
+fn value_key(value: &str, id: u64) -> Vec<u8> {
+    let mut key = value.as_bytes().to_vec();
+    key.push(0);
+    key.extend_from_slice(&id.to_be_bytes());
+    key
+}
+
+// Flipping the sign bit makes big-endian byte order match numeric order for negative times too
+pub(crate) fn sortable(seconds: i64) -> [u8; 8] {
+    ((seconds as u64) ^ (1 << 63)).to_be_bytes()
+}
+
+fn id_at_end(key: &[u8]) -> u64 {
+    let mut id = [0; 8];
+    id.copy_from_slice(&key[key.len() - 8..]);
+    u64::from_be_bytes(id)
+}
+
+fn prefix_ids(tree: &Tree, value: &str) -> Result<BTreeSet<u64>> {
+    let mut prefix = value.as_bytes().to_vec();
+    prefix.push(0);
+    tree.scan_prefix(prefix)
+        .keys()
+        .map(|key| Ok(id_at_end(&key?)))
+        .collect()
+}
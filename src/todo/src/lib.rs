@@ -0,0 +1,125 @@
+//! A todo list kept in an embedded sled database.
+//!
+//! [`TodoStore`] is the whole API: open a database, then insert, read, update and
+//! delete [`Todo`]s, or group several of those in a [`TodoStore::transaction`].
+//! Every change is logged and can be undone, and [`sync`] keeps several devices'
+//! lists the same. A database can be encrypted under a passphrase, see
+//! [`TodoStore::set_passphrase`]. Time spent on a todo can be logged and summed
+//! up, see [`timelog`].
+//! The `todo` binary is a command line frontend over it.
+
+use bincode::{Decode, Encode};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+
+mod crypto;
+pub mod dates;
+pub mod error;
+pub mod history;
+mod index;
+pub mod query;
+pub mod recur;
+mod schema;
+pub mod search;
+mod store;
+pub mod sync;
+pub mod timelog;
+pub mod transfer;
+
+pub use error::{Result, TodoError};
+pub use recur::Recurrence;
+pub use store::{TodoStore, Transaction, TxError, TxResult};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Encode, Decode, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Prior {
+    Fuck,   // Custom priority levels
+    High,
+    #[default]
+    Medium, // What `add` uses unless told otherwise
+    Soft,
+    Chill,
+}
+
+// Only `thing` is required when deserializing, so hand-written JSON can stay short
+#[derive(Debug, Clone, PartialEq, Encode, Decode, Serialize, Deserialize)]
+pub struct Todo {
+    pub thing: String,
+    #[serde(default)]
+    pub priority: Prior,
+    #[serde(default)]
+    pub done: bool,
+    #[serde(default)]
+    pub due: Option<i64>, // Unix seconds
+    #[serde(default)]
+    pub created: i64, // Unix seconds, 0 when unknown
+    #[serde(default)]
+    pub completed: Option<i64>, // Unix seconds, set while done
+    #[serde(default)]
+    pub tags: Vec<String>, // lowercase, without the leading `+`
+    #[serde(default)]
+    pub project: Option<String>,
+    #[serde(default)]
+    pub parent: Option<u64>, // this is a subtask of `parent`
+    #[serde(default)]
+    pub blocked_by: Vec<u64>, // not actionable until these are done
+    #[serde(default)]
+    pub recur: Option<Recurrence>, // comes back as a new todo once done
+    #[serde(default)]
+    pub notes: Option<String>, // free text, searched along with `thing`
+}
+
+impl std::str::FromStr for Prior {
+    type Err = TodoError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "fuck" => Ok(Prior::Fuck),
+            "high" => Ok(Prior::High),
+            "medium" => Ok(Prior::Medium),
+            "soft" => Ok(Prior::Soft),
+            "chill" => Ok(Prior::Chill),
+            _ => Err(TodoError::InvalidInput(format!(
+                "unknown priority '{}', use fuck, high, medium, soft or chill",
+                s
+            ))),
+        }
+    }
+}
+
+impl Todo {
+    pub fn new(thing: String, priority: Prior) -> Self {
+        Self {
+            thing,
+            priority,
+            done: false, // Defaults to not done
+            due: None,
+            created: Local::now().timestamp(),
+            completed: None,
+            tags: Vec::new(),
+            project: None,
+            parent: None,
+            blocked_by: Vec::new(),
+            recur: None,
+            notes: None,
+        }
+    }
+
+    pub fn is_overdue(&self, now: i64) -> bool {
+        !self.done && self.due.is_some_and(|due| due < now)
+    }
+
+    /// Marks the todo done or open again, keeping `completed` in step.
+    pub fn set_done(&mut self, done: bool) {
+        self.done = done;
+        self.completed = done.then(|| Local::now().timestamp());
+    }
+
+    pub(crate) fn serialize(listed: &Self) -> Vec<u8> {
+        schema::encode(listed) // Versioned header + bincode payload
+    }
+
+    pub(crate) fn deserialize(encoded: &[u8]) -> Result<Self> {
+        schema::decode(encoded) // Upgrades records written by older versions
+    }
+}
@@ -1,60 +1,752 @@
-use bincode::config::standard;
-use bincode::{Decode, Encode};
-
-use sled::Db; // embedded key-value store
+use chrono::{DateTime, Days, Local};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashSet};
 use std::env;
+use std::fs::File;
+use std::io::{self, BufWriter, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use zeroize::Zeroizing;
+
+use todo::history::{Event, EventKind};
+use todo::query::Query;
+use todo::sync;
+use todo::timelog;
+use todo::transfer::{self, Format};
+use todo::{dates, Prior, Recurrence, Result, Todo, TodoError, TodoStore};
+
+mod tui;
+
+const USAGE: &str = "usage:
+  todo                      interactive mode on a terminal, the open list otherwise
+  todo add <text> [+tag...] [project:<name>] [parent:<id>] [dep:<id>...]
+           [--priority <fuck|high|medium|soft|chill>] [--due <when>] [--repeat <rule>] [--notes <text>]
+  todo list [<filter>...]
+  todo search <words>...
+  todo next [<filter>...]
+  todo overdue
+  todo agenda [<days>]
+  todo done <id>...
+  todo reopen <id>...
+  todo rm <id>...
+  todo start <id>
+  todo stop
+  todo report [<from> [<to>]]
+  todo undo
+  todo redo
+  todo log [<count>]
+  todo migrate
+  todo export <json|csv|todo.txt> [<file>]
+  todo import <file> [--format <json|csv|todo.txt>]
+  todo serve [<address>]
+  todo sync <url>
+  todo encrypt | rekey | decrypt
+  todo edit <id> [<text>] [+tag] [-tag] [project:<name>|none] [parent:<id>|none] [dep:<id>|none] [-dep:<id>]
+            [--priority <level>] [--due <when>|none] [--repeat <rule>|none] [--notes <text>|none]
 
-#[derive(Debug, Encode, Decode)]
-enum Prior {
-    Fuck,   // Custom priority levels
-    High,
-    Medium,
-    Soft,
-    Chill,
+<when> is e.g. \"tomorrow 9am\", \"next fri\", \"in 3 days\" or \"2025-07-10 14:00\"
+<filter> terms must all match, e.g. project:infra +urgent -someday priority>=high !done due<7d due:none
+<rule> is daily, weekly, weekly:mon,thu, monthly, monthly:15 or after:3d (days after it is done)
+parent:<id> makes a subtask, dep:<id> waits until that todo is done; next shows what can be done now
+a word starting with \\ is text rather than an option, e.g. \\-draft or \\+1
+search ranks todos whose text or notes have every word, whole, as a prefix or with a typo, best first
+export writes to stdout without a <file>; import reads stdin from `-` and skips todos it already has
+start times work on a todo until stop, done or rm, one timer at a time; report adds it up per todo, tag and project
+over whole days, the last 7 unless given, each day being today, yesterday, a weekday or e.g. 2025-07-10;
+the time log stays on this device and outside undo
+undo and redo step through the changes in the log, one command at a time; `undo <id>` still reopens
+serve shares the list over HTTP, on 127.0.0.1:7878 unless given an address, and holds the database meanwhile
+sync <url> (e.g. http://10.0.0.2:7878) merges with a server both ways, the later edit of each field winning;
+IDs differ per device
+encrypt seals every todo under a passphrase, taken from TODO_PASSPHRASE or asked on the terminal (new ones
+are read from stdin when it is piped); tags, projects and due dates stay readable in the indexes, and so
+does the time log
+
+exit status: 2 invalid input, 3 no such todo, 4 unreadable record, 5 database locked,
+             6 database error, 7 I/O error, 8 sync failed, 9 passphrase missing or wrong";
+
+// Words `--due` looks at, enough for "next fri at 9am"
+const MAX_DATE_WORDS: usize = 4;
+
+// Flags and `+tag`/`-tag`/`project:`/`parent:`/`dep:` words given to add or edit
+#[derive(Default)]
+struct Options {
+    priority: Option<Prior>,
+    due: Option<Option<i64>>, // Some(None) clears the due date
+    repeat: Option<Option<Recurrence>>, // Some(None) stops it repeating
+    notes: Option<Option<String>>,      // Some(None) clears the notes
+    add_tags: Vec<String>,
+    remove_tags: Vec<String>,
+    project: Option<Option<String>>, // Some(None) clears the project
+    parent: Option<Option<u64>>,     // Some(None) makes it a top-level todo
+    clear_blockers: bool,
+    add_blockers: Vec<u64>,
+    remove_blockers: Vec<u64>,
 }
 
-#[derive(Debug, Encode, Decode)]
-struct Todo {
-    thing: String,
-    priority: Prior,
-    done: bool,
+impl Options {
+    fn apply(self, todo: &mut Todo) {
+        if let Some(priority) = self.priority {
+            todo.priority = priority;
+        }
+        if let Some(due) = self.due {
+            todo.due = due;
+        }
+        if let Some(repeat) = self.repeat {
+            // A bare weekly or monthly repeats on the weekday or day of the due date
+            let anchor = todo.due.map_or_else(Local::now, dates::from_timestamp);
+            todo.recur = repeat.map(|rule| rule.anchored(anchor));
+        }
+        if let Some(notes) = self.notes {
+            todo.notes = notes;
+        }
+        if let Some(project) = self.project {
+            todo.project = project;
+        }
+        if let Some(parent) = self.parent {
+            todo.parent = parent;
+        }
+        todo.tags.retain(|tag| !self.remove_tags.contains(tag));
+        for tag in self.add_tags {
+            if !todo.tags.contains(&tag) {
+                todo.tags.push(tag);
+            }
+        }
+        if self.clear_blockers {
+            todo.blocked_by.clear();
+        }
+        todo.blocked_by.retain(|id| !self.remove_blockers.contains(id));
+        for id in self.add_blockers {
+            if !todo.blocked_by.contains(&id) {
+                todo.blocked_by.push(id);
+            }
+        }
+    }
 }
 
 fn main() {
-    // Example usage of serialization/deserialization (commented out)
-    // let todo = Todo::new("Studiare meglio rust".into(), Prior::Fuck);
-    // let encoded = Todo::serialize(&todo);
-    // let decoded = Todo::deserialize(&encoded);
-    // println!("Original: {:#?}", decoded);
-    // println!("Modified {:#?}", encoded);
-
     let args: Vec<String> = env::args().collect(); // Collect command-line arguments
 
-    if args.len() > 1 {
-        let command = &args[1];
-        match &command[..] {
-            _ => println!("{:#?}", &args[2..]), // Just prints the remaining arguments for now
+    if let Err(e) = run(&args[1..]) {
+        eprintln!("todo: {}", e);
+        process::exit(e.exit_code());
+    }
+}
+
+fn run(args: &[String]) -> Result<()> {
+    let mut store = TodoStore::open_with(db_path(), passphrase)?;
+
+    let Some(command) = args.first() else {
+        if io::stdin().is_terminal() && io::stdout().is_terminal() {
+            return tui::run(&store);
+        }
+        return list(&store, &[]); // Piped, just show the list
+    };
+    let rest = &args[1..];
+
+    match &command[..] {
+        "add" => {
+            let (words, options) = parse_options(rest, false)?;
+            if words.is_empty() {
+                return Err(TodoError::InvalidInput("nothing to add".into()));
+            }
+            let mut todo = Todo::new(words.join(" "), Prior::Medium);
+            options.apply(&mut todo);
+            let id = store.insert(&todo)?;
+            println!("added #{}", id);
+        }
+        "list" | "ls" => list(&store, rest)?,
+        "next" => next(&store, rest)?,
+        "search" => {
+            let now = Local::now();
+            for (id, todo) in store.search(&rest.join(" "))? {
+                print_item(&store, id, &todo, now, 0)?;
+                for line in todo.notes.iter().flat_map(|notes| notes.lines()) {
+                    println!("{:33}{}", "", line); // lined up under the thing
+                }
+            }
+        }
+        "overdue" => overdue(&store)?,
+        "agenda" => {
+            let days = match rest.first() {
+                Some(days) => days
+                    .parse()
+                    .map_err(|_| TodoError::InvalidInput(format!("'{}' is not a number of days", days)))?,
+                None => 7,
+            };
+            agenda(&store, days)?;
+        }
+        "undo" | "redo" if rest.is_empty() => {
+            let event = if command == "undo" { store.undo()? } else { store.redo()? };
+            match event {
+                Some(event) => println!("{} {}", command, describe(&event)),
+                None => println!("nothing to {}", command),
+            }
+        }
+        "log" => {
+            let count = match rest.first() {
+                Some(count) => count
+                    .parse()
+                    .map_err(|_| TodoError::InvalidInput(format!("'{}' is not a number of events", count)))?,
+                None => 20,
+            };
+            log(&store, count)?;
+        }
+        // `undo <id>` is the old name of reopen
+        "done" | "reopen" | "undo" => {
+            let done = command == "done";
+            let mut ids = parse_ids(rest)?;
+            if done {
+                for id in ids.clone() {
+                    let open: Vec<u64> = open_descendants(&store, id)?
+                        .into_iter()
+                        .filter(|child| !ids.contains(child))
+                        .collect();
+                    if open.is_empty() {
+                        continue;
+                    }
+                    let list: Vec<String> = open.iter().map(|id| format!("#{}", id)).collect();
+                    let question = format!(
+                        "#{} has {} open subtask(s): {}. Complete them too?",
+                        id,
+                        open.len(),
+                        list.join(", ")
+                    );
+                    if confirm(&question)? {
+                        ids.extend(open);
+                    }
+                }
+            }
+            let spawned = store.transaction(|tx| {
+                let mut spawned = Vec::new();
+                for &id in &ids {
+                    if done {
+                        spawned.extend(tx.complete(id)?);
+                    } else {
+                        tx.reopen(id)?;
+                    }
+                }
+                Ok(spawned)
+            })?;
+            for id in spawned {
+                let due = store.get(id)?.due.unwrap_or_default();
+                println!("repeats as #{}, due {}", id, dates::show(dates::from_timestamp(due), Local::now()));
+            }
+        }
+        "serve" => {
+            let server = sync::Server::bind(rest.first().map_or("127.0.0.1:7878", String::as_str))?;
+            eprintln!("serving on {}, ctrl-c stops", server.addr());
+            server.run(&store)?;
+        }
+        "sync" => {
+            let [url] = rest else {
+                return Err(TodoError::InvalidInput("sync needs the server url, e.g. http://10.0.0.2:7878".into()));
+            };
+            let synced = sync::sync(&store, url)?;
+            println!("sent {}, received {}", synced.sent, synced.received);
+        }
+        "encrypt" | "rekey" => {
+            match (command.as_str(), store.is_encrypted()) {
+                ("encrypt", true) => return Err(TodoError::InvalidInput("already encrypted, rekey changes it".into())),
+                ("rekey", false) => return Err(TodoError::InvalidInput("not encrypted, use encrypt".into())),
+                _ => {}
+            }
+            store.set_passphrase(Some(&new_passphrase()?))?;
+            println!("{}", if command == "encrypt" { "encrypted" } else { "passphrase changed" });
+        }
+        "decrypt" => {
+            if !store.is_encrypted() {
+                return Err(TodoError::InvalidInput("not encrypted".into()));
+            }
+            store.set_passphrase(None)?;
+            println!("decrypted");
+        }
+        "start" => {
+            let id = parse_id(rest)?;
+            let now = Local::now();
+            store.start(id, now.timestamp())?;
+            println!("started #{} {} at {}", id, store.get(id)?.thing, now.format("%H:%M"));
+        }
+        "stop" => match store.stop(Local::now().timestamp())? {
+            Some(interval) => {
+                let seconds = interval.end.unwrap_or(interval.start) - interval.start;
+                println!("stopped #{} after {}", interval.id, duration(seconds));
+            }
+            None => println!("no timer running"),
+        },
+        "report" => report(&store, rest)?,
+        "rm" => {
+            let ids = parse_ids(rest)?;
+            store.transaction(|tx| ids.iter().try_for_each(|&id| tx.delete(id).map(|_| ())))?;
+        }
+        "edit" => {
+            let id = parse_id(rest)?;
+            let (words, options) = parse_options(&rest[1..], true)?;
+            let mut todo = store.get(id)?;
+            if !words.is_empty() {
+                todo.thing = words.join(" ");
+            }
+            options.apply(&mut todo);
+            store.update(id, &todo)?;
+        }
+        "migrate" => {
+            let upgraded = store.migrate()?;
+            println!("upgraded {} record(s) to the current format", upgraded);
+        }
+        "export" => {
+            let format: Format = rest
+                .first()
+                .ok_or_else(|| TodoError::InvalidInput("export needs a format: json, csv or todo.txt".into()))?
+                .parse()?;
+            let items = store.iter().collect::<Result<Vec<_>>>()?;
+            match rest.get(1) {
+                Some(path) => transfer::export(&items, format, BufWriter::new(File::create(path)?))?,
+                None => transfer::export(&items, format, io::stdout().lock())?,
+            }
+        }
+        "import" => {
+            let (path, format) = parse_import(rest)?;
+            let todos = if path == "-" {
+                let mut input = Vec::new();
+                io::stdin().read_to_end(&mut input)?;
+                transfer::parse(format, &input[..])?
+            } else {
+                transfer::parse(format, File::open(path)?)?
+            };
+            let imported = transfer::import(&store, &todos)?;
+            println!(
+                "imported {} todo(s), skipped {} duplicate(s)",
+                imported.added.len(),
+                imported.duplicates
+            );
+        }
+        "help" | "-h" | "--help" => println!("{}", USAGE),
+        other => return Err(TodoError::InvalidInput(format!("unknown command '{}'\n{}", other, USAGE))),
+    }
+
+    store.flush()
+}
+
+/// `$TODO_DB`, else `todo` in the user's data directory.
+fn db_path() -> PathBuf {
+    if let Some(path) = env::var_os("TODO_DB") {
+        return PathBuf::from(path);
+    }
+    env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("share")))
+        .unwrap_or_default()
+        .join("todo")
+}
+
+// Soonest due first, undated last, then most urgent priority
+fn sort(items: &mut [(u64, Todo)]) {
+    items.sort_by_key(|(id, todo)| (todo.due.is_none(), todo.due, todo.priority, *id));
+}
+
+// `depth` indents subtasks under their parent; open blockers show as `dep:<id>`
+fn print_item(store: &TodoStore, id: u64, todo: &Todo, now: DateTime<Local>, depth: usize) -> Result<()> {
+    println!("{}", format_item(store, id, todo, now, depth)?);
+    Ok(())
+}
+
+fn format_item(store: &TodoStore, id: u64, todo: &Todo, now: DateTime<Local>, depth: usize) -> Result<String> {
+    let due = todo.due.map_or(String::new(), |due| dates::show(dates::from_timestamp(due), now));
+    let mut thing = "  ".repeat(depth) + &todo.thing;
+    if let Some(project) = &todo.project {
+        thing.push_str(&format!(" project:{}", project));
+    }
+    for tag in &todo.tags {
+        thing.push_str(&format!(" +{}", tag));
+    }
+    for blocker in store.open_blockers(todo)? {
+        thing.push_str(&format!(" dep:{}", blocker));
+    }
+    if let Some(rule) = todo.recur {
+        thing.push_str(&format!(" repeat:{}", rule));
+    }
+    Ok(format!(
+        "{:>4} [{}] {:<6} {:<16} {}",
+        id,
+        if todo.done { "x" } else { " " },
+        format!("{:?}", todo.priority),
+        due,
+        thing
+    ))
+}
+
+fn list(store: &TodoStore, filter: &[String]) -> Result<()> {
+    let now = Local::now();
+    let mut items = store.query(&Query::parse(filter, now)?)?;
+    sort(&mut items);
+    for ((id, todo), depth) in tree(&items) {
+        print_item(store, *id, todo, now, depth)?;
+    }
+    Ok(())
+}
+
+// Sorted items in display order with their depth: subtasks under their parent, and
+// ones whose parent is not in `items` starting a tree of their own
+fn tree(items: &[(u64, Todo)]) -> Vec<(&(u64, Todo), usize)> {
+    let listed: HashSet<u64> = items.iter().map(|(id, _)| *id).collect();
+    let mut children: BTreeMap<u64, Vec<&(u64, Todo)>> = BTreeMap::new();
+    let mut roots = Vec::new();
+    for item in items {
+        match item.1.parent.filter(|parent| listed.contains(parent)) {
+            Some(parent) => children.entry(parent).or_default().push(item),
+            None => roots.push(item),
+        }
+    }
+
+    let mut ordered = Vec::new();
+    let mut stack: Vec<(&(u64, Todo), usize)> = roots.into_iter().rev().map(|item| (item, 0)).collect();
+    while let Some((item, depth)) = stack.pop() {
+        ordered.push((item, depth));
+        if let Some(subtasks) = children.get(&item.0) {
+            stack.extend(subtasks.iter().rev().map(|item| (*item, depth + 1)));
         }
+    }
+    ordered
+}
+
+// Open todos with nothing left to wait on: no open blockers and no open subtasks,
+// most urgent first
+fn next(store: &TodoStore, filter: &[String]) -> Result<()> {
+    let now = Local::now();
+    let mut items = Vec::new();
+    for (id, todo) in store.query(&Query::parse(filter, now)?)? {
+        if todo.done || !store.open_blockers(&todo)?.is_empty() {
+            continue;
+        }
+        if store.children(id)?.iter().any(|(_, child)| !child.done) {
+            continue;
+        }
+        items.push((id, todo));
+    }
+    items.sort_by_key(|(id, todo)| (todo.priority, todo.due.is_none(), todo.due, *id));
+    for (id, todo) in &items {
+        print_item(store, *id, todo, now, 0)?;
+    }
+    Ok(())
+}
+
+// Newest first: `  12 2025-07-10 14:03  done #3 water plants, add #9 water plants`
+fn log(store: &TodoStore, count: usize) -> Result<()> {
+    for event in store.history().take(count) {
+        let event = event?;
+        let at = dates::from_timestamp(event.at).format("%Y-%m-%d %H:%M");
+        println!("{:>5} {}  {}", event.seq, at, describe(&event));
+    }
+    Ok(())
+}
+
+fn describe(event: &Event) -> String {
+    let changes: Vec<String> = event
+        .changes
+        .iter()
+        .map(|change| {
+            let thing = change.after.as_ref().or(change.before.as_ref()).map_or("", |todo| &todo.thing);
+            format!("{} #{} {}", change.action(), change.id, thing)
+        })
+        .collect();
+    match event.kind {
+        EventKind::Change => changes.join(", "),
+        EventKind::Undo(of) => format!("(undo of {}) {}", of, changes.join(", ")),
+        EventKind::Redo(of) => format!("(redo of {}) {}", of, changes.join(", ")),
+        EventKind::Sync => format!("(sync) {}", changes.join(", ")),
+    }
+}
+
+// Every open todo below `id`, depth first
+fn open_descendants(store: &TodoStore, id: u64) -> Result<Vec<u64>> {
+    let mut open = Vec::new();
+    let mut stack = vec![id];
+    while let Some(parent) = stack.pop() {
+        for (child, todo) in store.children(parent)? {
+            if !todo.done {
+                open.push(child);
+            }
+            stack.push(child);
+        }
+    }
+    Ok(open)
+}
+
+// Only asked for when the database turns out to be encrypted
+fn passphrase() -> Result<Zeroizing<String>> {
+    if let Some(passphrase) = env::var_os("TODO_PASSPHRASE") {
+        return Ok(Zeroizing::new(passphrase.to_string_lossy().into_owned()));
+    }
+    rpassword::prompt_password("passphrase: ").map(Zeroizing::new).map_err(|_| {
+        TodoError::Passphrase("the database is encrypted, set TODO_PASSPHRASE or run on a terminal".into())
+    })
+}
+
+// Typed twice on a terminal, or the first line of piped stdin
+fn new_passphrase() -> Result<Zeroizing<String>> {
+    let passphrase = if io::stdin().is_terminal() {
+        let first = Zeroizing::new(rpassword::prompt_password("new passphrase: ")?);
+        let again = Zeroizing::new(rpassword::prompt_password("again: ")?);
+        if first != again {
+            return Err(TodoError::InvalidInput("the passphrases differ".into()));
+        }
+        first
     } else {
-        // todo.list(); // Placeholder for future implementation
+        let mut line = Zeroizing::new(String::new());
+        io::stdin().read_line(&mut line)?;
+        Zeroizing::new(line.trim_end_matches(['\r', '\n']).to_string())
+    };
+    if passphrase.is_empty() {
+        return Err(TodoError::InvalidInput("the passphrase is empty".into()));
+    }
+    Ok(passphrase)
+}
+
+// Asks on the terminal; without one the answer is no
+fn confirm(question: &str) -> Result<bool> {
+    if !io::stdin().is_terminal() {
+        eprintln!("todo: {} (not asking without a terminal, leaving them open)", question);
+        return Ok(false);
+    }
+    print!("{} [y/N] ", question);
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+fn overdue(store: &TodoStore) -> Result<()> {
+    let now = Local::now();
+    let mut items: Vec<(u64, Todo)> = store
+        .iter()
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .filter(|(_, todo)| todo.is_overdue(now.timestamp()))
+        .collect();
+    sort(&mut items);
+    for (id, todo) in &items {
+        print_item(store, *id, todo, now, 0)?;
+    }
+    Ok(())
+}
+
+// Open items due in the next `days` days, grouped by day, after anything already overdue
+fn agenda(store: &TodoStore, days: u64) -> Result<()> {
+    let now = Local::now();
+    let mut items: Vec<(u64, Todo)> = store
+        .iter()
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .filter(|(_, todo)| !todo.done && todo.due.is_some())
+        .collect();
+    sort(&mut items);
+
+    let overdue: Vec<&(u64, Todo)> = items.iter().filter(|(_, t)| t.is_overdue(now.timestamp())).collect();
+    if !overdue.is_empty() {
+        println!("Overdue");
+        for (id, todo) in overdue {
+            print_item(store, *id, todo, now, 0)?;
+        }
+    }
+
+    let today = now.date_naive();
+    for offset in 0..days {
+        let day = today + Days::new(offset);
+        let due_that_day: Vec<&(u64, Todo)> = items
+            .iter()
+            .filter(|(_, t)| !t.is_overdue(now.timestamp()))
+            .filter(|(_, t)| t.due.map(|due| dates::from_timestamp(due).date_naive()) == Some(day))
+            .collect();
+        if due_that_day.is_empty() {
+            continue;
+        }
+        println!("{}", day.format("%A %d %B"));
+        for (id, todo) in due_that_day {
+            print_item(store, *id, todo, now, 0)?;
+        }
     }
+    Ok(())
 }
 
-impl Todo {
-    fn new(thing: String, priority: Prior) -> Self {
-        Self {
-            thing,
-            priority,
-            done: false, // Defaults to not done
+// Time per todo, tag and project, most first, over the whole days from `<from>` to `<to>`
+fn report(store: &TodoStore, args: &[String]) -> Result<()> {
+    let now = Local::now();
+    let today = now.date_naive();
+    let day = |text: &String| dates::parse_day(text, today).map_err(TodoError::InvalidInput);
+    let (from, to) = match args {
+        [] => (today - Days::new(6), today),
+        [from] => (day(from)?, today),
+        [from, to] => (day(from)?, day(to)?),
+        _ => return Err(TodoError::InvalidInput("report takes at most two days, <from> and <to>".into())),
+    };
+    if from > to {
+        return Err(TodoError::InvalidInput(format!("the report ends on {}, before it starts", to)));
+    }
+    let start = dates::day_start(from).timestamp();
+    let end = dates::day_start(to + Days::new(1)).timestamp();
+    let report = timelog::report(store, start, end, now.timestamp())?;
+
+    println!("{} to {}", from, to);
+    let mut tasks: Vec<(u64, i64)> = report.tasks.into_iter().collect();
+    tasks.sort_by_key(|&(id, seconds)| (Reverse(seconds), id));
+    for (id, seconds) in tasks {
+        let thing = match store.get(id) {
+            Ok(todo) => todo.thing,
+            Err(TodoError::NotFound(_)) => "(removed)".into(),
+            Err(e) => return Err(e),
+        };
+        println!("{:>4} {:>8}  {}", id, duration(seconds), thing);
+    }
+    for (label, totals) in [("+", report.tags), ("project:", report.projects)] {
+        let mut totals: Vec<(String, i64)> = totals.into_iter().collect();
+        totals.sort_by(|(a, a_seconds), (b, b_seconds)| b_seconds.cmp(a_seconds).then(a.cmp(b)));
+        for (name, seconds) in totals {
+            println!("{:>4} {:>8}  {}{}", "", duration(seconds), label, name);
         }
     }
+    println!("{:>4} {:>8}  total", "", duration(report.total));
+    if let Some(running) = store.running()? {
+        let since = dates::show(dates::from_timestamp(running.start), now);
+        println!("the timer is running on #{} since {}", running.id, since);
+    }
+    Ok(())
+}
+
+// `2h 05m`, rounded down to the minute
+fn duration(seconds: i64) -> String {
+    let minutes = seconds / 60;
+    format!("{}h {:02}m", minutes / 60, minutes % 60)
+}
+
+fn parse_id(args: &[String]) -> Result<u64> {
+    let arg = args.first().ok_or_else(|| TodoError::InvalidInput("missing <id>".into()))?;
+    id_from(arg)
+}
 
-    fn serialize(listed: &Self) -> Vec<u8> {
-        bincode::encode_to_vec(listed, standard()).unwrap() // Serializes the Todo to bytes
+// One or more IDs, all of which must parse
+fn parse_ids(args: &[String]) -> Result<Vec<u64>> {
+    if args.is_empty() {
+        return Err(TodoError::InvalidInput("missing <id>".into()));
     }
+    let mut ids = Vec::new();
+    for arg in args {
+        let id = id_from(arg)?;
+        if !ids.contains(&id) {
+            ids.push(id);
+        }
+    }
+    Ok(ids)
+}
+
+fn id_from(text: &str) -> Result<u64> {
+    text.trim_start_matches('#')
+        .parse()
+        .map_err(|_| TodoError::InvalidInput(format!("'{}' is not a todo id", text)))
+}
 
-    fn deserialize(encoded: &[u8]) -> Self {
-        let (decoded, _) = bincode::decode_from_slice(encoded, standard()).unwrap(); // Deserializes back to a Todo
-        decoded
+// `<file> [--format <format>]`, guessing the format from the extension when not given
+fn parse_import(args: &[String]) -> Result<(&str, Format)> {
+    let mut path = None;
+    let mut format = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--format" | "-f" => {
+                let name = iter.next().ok_or_else(|| TodoError::InvalidInput("--format needs a format".into()))?;
+                format = Some(name.parse()?);
+            }
+            _ => path = Some(arg.as_str()),
+        }
     }
+    let path = path.ok_or_else(|| TodoError::InvalidInput("import needs a file, or - for stdin".into()))?;
+    let format = format.or_else(|| Format::from_path(Path::new(path))).ok_or_else(|| {
+        TodoError::InvalidInput(format!("cannot tell the format of '{}', pass --format", path))
+    })?;
+    Ok((path, format))
+}
+
+// Splits `--priority <level>` (or `-p`), `--due <when>` (or `-d`), `--repeat <rule>` (or `-r`),
+// `+tag`, `-tag`, `project:<name>`, `parent:<id>`, `dep:<id>` and `-dep:<id>` out of the remaining words.
+// `-tag` only removes a tag when `editing`, and only if it starts with a letter, so `-5` stays text.
+fn parse_options(args: &[String], editing: bool) -> Result<(Vec<String>, Options)> {
+    let mut words = Vec::new();
+    let mut options = Options::default();
+    let mut iter = args.iter();
+
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--priority" | "-p" => {
+                let level = iter.next().ok_or_else(|| TodoError::InvalidInput("--priority needs a level".into()))?;
+                options.priority = Some(level.parse()?);
+            }
+            "--repeat" | "-r" => {
+                let rule = iter.next().ok_or_else(|| TodoError::InvalidInput("--repeat needs a rule".into()))?;
+                options.repeat = Some(match rule.as_str() {
+                    "none" => None,
+                    rule => Some(rule.parse()?),
+                });
+            }
+            "--due" | "-d" => {
+                let ahead = iter.as_slice();
+                let when = ahead.first().ok_or_else(|| TodoError::InvalidInput("--due needs a date".into()))?;
+                if when == "none" {
+                    iter.next();
+                    options.due = Some(None);
+                    continue;
+                }
+                // The longest run of words that reads as a date, so `--due tomorrow 9am` takes both
+                let now = Local::now();
+                let longest = (2..=ahead.len().min(MAX_DATE_WORDS))
+                    .rev()
+                    .find_map(|n| dates::parse_when(&ahead[..n].join(" "), now).ok().map(|at| (n, at)));
+                let (taken, at) = match longest {
+                    Some(found) => found,
+                    None => (1, dates::parse_when(when, now).map_err(TodoError::InvalidInput)?),
+                };
+                iter.nth(taken - 1);
+                options.due = Some(Some(at.timestamp()));
+            }
+            "--notes" | "-n" => {
+                let notes = iter.next().ok_or_else(|| TodoError::InvalidInput("--notes needs a text".into()))?;
+                options.notes = Some((notes != "none" && !notes.is_empty()).then(|| notes.clone()));
+            }
+            "project:none" => options.project = Some(None),
+            "parent:none" => options.parent = Some(None),
+            "dep:none" => options.clear_blockers = true,
+            _ => {
+                if let Some(text) = arg.strip_prefix('\\') {
+                    words.push(text.to_string());
+                } else if let Some(parent) = arg.strip_prefix("parent:") {
+                    options.parent = Some(Some(id_from(parent)?));
+                } else if let Some(blocker) = arg.strip_prefix("dep:") {
+                    options.add_blockers.push(id_from(blocker)?);
+                } else if let Some(blocker) = arg.strip_prefix("-dep:") {
+                    options.remove_blockers.push(id_from(blocker)?);
+                } else if let Some(project) = arg.strip_prefix("project:").filter(|p| !p.is_empty()) {
+                    options.project = Some(Some(project.to_lowercase()));
+                } else if let Some(tag) = arg.strip_prefix('+').filter(|t| !t.is_empty()) {
+                    options.add_tags.push(tag.to_lowercase());
+                } else if let Some(tag) = arg.strip_prefix('-').filter(|t| editing && starts_with_letter(t)) {
+                    options.remove_tags.push(tag.to_lowercase());
+                } else {
+                    words.push(arg.clone());
+                }
+            }
+        }
+    }
+    Ok((words, options))
+}
+
+// Puts a `\` before the words of `text` that `parse_options` would not read back as
+// themselves, so text put back into an edit prompt stays text
+fn escape_options(text: &str) -> String {
+    let escape = |word: &str| match parse_options(&[word.to_string()], true) {
+        Ok((words, _)) if words == [word] => word.to_string(),
+        _ => format!("\\{}", word),
+    };
+    text.split(' ').map(escape).collect::<Vec<_>>().join(" ")
+}
+
+fn starts_with_letter(word: &str) -> bool {
+    word.starts_with(char::is_alphabetic)
 }
@@ -0,0 +1,214 @@
+use chrono::{DateTime, Duration, Local};
+
+use crate::error::{Result, TodoError};
+use crate::{dates, Prior, Todo};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Cmp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Cmp {
+    fn holds<T: PartialOrd>(self, left: T, right: T) -> bool {
+        match self {
+            Cmp::Eq => left == right,
+            Cmp::Ne => left != right,
+            Cmp::Lt => left < right,
+            Cmp::Le => left <= right,
+            Cmp::Gt => left > right,
+            Cmp::Ge => left >= right,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Term {
+    Project(String),
+    Tag(String),
+    NotTag(String),
+    /// Compares urgency, so `priority>=high` means High or Fuck
+    Priority(Cmp, Prior),
+    Done(bool),
+    Due(Cmp, i64),
+    Undated,
+    Dated,
+    Text(String),
+}
+
+/// A `list` filter: every term must match.
+///
+/// `project:infra +urgent -someday priority>=high !done due<7d due:none "some words"`
+#[derive(Debug, Default)]
+pub struct Query {
+    pub terms: Vec<Term>,
+}
+
+impl Query {
+    pub fn parse(args: &[String], now: DateTime<Local>) -> Result<Self> {
+        let terms = args
+            .iter()
+            .map(|arg| parse_term(arg, now))
+            .collect::<Result<_>>()?;
+        Ok(Query { terms })
+    }
+
+    pub fn matches(&self, todo: &Todo) -> bool {
+        self.terms.iter().all(|term| match term {
+            Term::Project(project) => todo.project.as_deref() == Some(project.as_str()),
+            Term::Tag(tag) => todo.tags.contains(tag),
+            Term::NotTag(tag) => !todo.tags.contains(tag),
+            Term::Priority(cmp, level) => cmp.holds(urgency(todo.priority), urgency(*level)),
+            Term::Done(done) => todo.done == *done,
+            Term::Due(cmp, at) => todo.due.is_some_and(|due| cmp.holds(due, *at)),
+            Term::Undated => todo.due.is_none(),
+            Term::Dated => todo.due.is_some(),
+            Term::Text(text) => todo.thing.to_lowercase().contains(text),
+        })
+    }
+}
+
+fn urgency(priority: Prior) -> u8 {
+    4 - priority as u8 // Fuck is declared first
+}
+
+fn parse_term(arg: &str, now: DateTime<Local>) -> Result<Term> {
+    match arg {
+        "done" => return Ok(Term::Done(true)),
+        "!done" => return Ok(Term::Done(false)),
+        _ => {}
+    }
+    if let Some(tag) = arg.strip_prefix('+').filter(|t| !t.is_empty()) {
+        return Ok(Term::Tag(tag.to_lowercase()));
+    }
+    if let Some(tag) = arg.strip_prefix('-').filter(|t| !t.is_empty()) {
+        return Ok(Term::NotTag(tag.to_lowercase()));
+    }
+
+    // Other words with a `:` or `<` in them, like `http://host`, are text to look for
+    let (field, cmp, value) = match split_comparison(arg) {
+        Some((field @ ("project" | "pro" | "priority" | "pri" | "due"), cmp, value)) => (field, cmp, value),
+        _ => return Ok(Term::Text(arg.to_lowercase())),
+    };
+    match field {
+        "project" | "pro" if cmp == Cmp::Eq => Ok(Term::Project(value.to_lowercase())),
+        "priority" | "pri" => Ok(Term::Priority(cmp, value.parse()?)),
+        "due" => match value {
+            "none" if cmp == Cmp::Eq => Ok(Term::Undated),
+            "any" if cmp == Cmp::Eq => Ok(Term::Dated),
+            _ => Ok(Term::Due(cmp, parse_bound(value, now)?)),
+        },
+        _ => Err(TodoError::InvalidInput(format!("cannot filter on '{}'", arg))),
+    }
+}
+
+// Longest operators first so `>=` is not read as `>`
+fn split_comparison(arg: &str) -> Option<(&str, Cmp, &str)> {
+    const OPERATORS: [(&str, Cmp); 7] = [
+        (">=", Cmp::Ge),
+        ("<=", Cmp::Le),
+        ("!=", Cmp::Ne),
+        (">", Cmp::Gt),
+        ("<", Cmp::Lt),
+        ("=", Cmp::Eq),
+        (":", Cmp::Eq),
+    ];
+    let start = arg.find(['<', '>', '=', '!', ':'])?;
+    let (field, rest) = arg.split_at(start);
+    let (op, cmp) = OPERATORS.iter().find(|(op, _)| rest.starts_with(op))?;
+    Some((field, *cmp, &rest[op.len()..]))
+}
+
+// `7d`, `12h`, `2w` are offsets from now, anything else goes through the date parser
+fn parse_bound(value: &str, now: DateTime<Local>) -> Result<i64> {
+    let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let (amount, unit) = value.split_at(split);
+    let offset = match (amount.parse::<i64>(), unit) {
+        (Ok(n), "h") => Some(Duration::hours(n)),
+        (Ok(n), "d") => Some(Duration::days(n)),
+        (Ok(n), "w") => Some(Duration::weeks(n)),
+        _ => None,
+    };
+    match offset {
+        Some(offset) => Ok((now + offset).timestamp()),
+        None => dates::parse_when(&value.replace('_', " "), now)
+            .map(|at| at.timestamp())
+            .map_err(TodoError::InvalidInput),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dates::tests::at;
+
+    fn parse(args: &[&str]) -> Result<Query> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        Query::parse(&args, at("2026-03-10 14:00"))
+    }
+
+    fn todo(priority: Prior, tags: &[&str]) -> Todo {
+        let mut todo = Todo::new("Renew passport".into(), priority);
+        todo.tags = tags.iter().map(|tag| tag.to_string()).collect();
+        todo
+    }
+
+    #[test]
+    fn terms() {
+        let now = at("2026-03-10 14:00").timestamp();
+        let query = parse(&["priority>=high", "due<7d", "!done", "-someday", "+Home", "PASS"]).unwrap();
+        assert_eq!(
+            query.terms,
+            [
+                Term::Priority(Cmp::Ge, Prior::High),
+                Term::Due(Cmp::Lt, now + 7 * 24 * 3600),
+                Term::Done(false),
+                Term::NotTag("someday".into()),
+                Term::Tag("home".into()),
+                Term::Text("pass".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn matching() {
+        let urgent = parse(&["priority>=high", "!done", "-someday"]).unwrap();
+        assert!(urgent.matches(&todo(Prior::Fuck, &[])));
+        assert!(urgent.matches(&todo(Prior::High, &["home"])));
+        assert!(!urgent.matches(&todo(Prior::Medium, &[])));
+        assert!(!urgent.matches(&todo(Prior::High, &["someday"])));
+        let mut done = todo(Prior::High, &[]);
+        done.set_done(true);
+        assert!(!urgent.matches(&done));
+
+        let soon = parse(&["due<7d"]).unwrap();
+        let mut due = todo(Prior::Medium, &[]);
+        assert!(!soon.matches(&due), "undated todos are never due soon");
+        due.due = Some(at("2026-03-12 09:00").timestamp());
+        assert!(soon.matches(&due));
+        due.due = Some(at("2026-03-20 09:00").timestamp());
+        assert!(!soon.matches(&due));
+    }
+
+    #[test]
+    fn bad_filters_are_refused() {
+        assert!(matches!(parse(&["priority>=urgent"]), Err(TodoError::InvalidInput(_))));
+        assert!(matches!(parse(&["project<infra"]), Err(TodoError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn other_words_with_operators_are_text() {
+        let query = parse(&["http://host", "ratio:2", "colour=red"]).unwrap();
+        assert_eq!(
+            query.terms,
+            [Term::Text("http://host".into()), Term::Text("ratio:2".into()), Term::Text("colour=red".into())]
+        );
+        let mut todo = todo(Prior::Medium, &[]);
+        todo.thing = "Check http://host at ratio:2 with colour=red".into();
+        assert!(query.matches(&todo));
+    }
+}
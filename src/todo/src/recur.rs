@@ -0,0 +1,249 @@
+use bincode::{Decode, Encode};
+use chrono::{DateTime, Datelike, Days, Duration, Local, Months, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Weekday};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::dates::{self, END_OF_DAY};
+use crate::error::{Result, TodoError};
+
+const WEEKDAYS: [(&str, Weekday); 7] = [
+    ("mon", Weekday::Mon),
+    ("tue", Weekday::Tue),
+    ("wed", Weekday::Wed),
+    ("thu", Weekday::Thu),
+    ("fri", Weekday::Fri),
+    ("sat", Weekday::Sat),
+    ("sun", Weekday::Sun),
+];
+
+/// When a todo comes back after it is done. Written as `daily`, `weekly`,
+/// `weekly:mon,thu`, `monthly`, `monthly:15` or `after:3d`.
+///
+/// The schedule rules count from the due date, so finishing late does not shift
+/// them; `AfterDone` counts from the completion instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub enum Recurrence {
+    Daily,
+    /// On the weekdays whose bit is set, bit 0 being Monday. 0 until [`anchored`](Self::anchored).
+    Weekly(u8),
+    /// On this day of the month, or the last day of shorter months. 0 until anchored.
+    Monthly(u8),
+    /// This many days after the previous occurrence was completed
+    AfterDone(u16),
+}
+
+impl Recurrence {
+    /// Fills in what a bare `weekly` or `monthly` left open from the first due date.
+    pub fn anchored(self, at: DateTime<Local>) -> Self {
+        match self {
+            Recurrence::Weekly(0) => Recurrence::Weekly(1 << at.weekday().num_days_from_monday()),
+            Recurrence::Monthly(0) => Recurrence::Monthly(at.day() as u8),
+            other => other,
+        }
+    }
+
+    /// The due date of the occurrence after one due at `due` and completed at
+    /// `completed`. It keeps the time of day of `due`, and is always after
+    /// `completed` so occurrences missed meanwhile are skipped.
+    pub fn next_due(self, due: Option<i64>, completed: DateTime<Local>) -> i64 {
+        let due = due.map(dates::from_timestamp);
+        let time = due.map_or(end_of_day(), |due| due.time());
+        let start = due.map_or(completed.date_naive(), |due| due.date_naive());
+
+        if let Recurrence::AfterDone(days) = self {
+            return local(completed.date_naive() + Days::new(days.into()), time);
+        }
+        let mut day = self.step(start);
+        while local(day, time) <= completed.timestamp() {
+            day = self.step(day);
+        }
+        local(day, time)
+    }
+
+    // The first scheduled day after `day`
+    fn step(self, day: NaiveDate) -> NaiveDate {
+        match self {
+            Recurrence::Daily | Recurrence::AfterDone(_) => day + Days::new(1),
+            Recurrence::Weekly(mask) => {
+                let mask = if mask == 0 { 1 << day.weekday().num_days_from_monday() } else { mask };
+                (1..=7)
+                    .map(|ahead| day + Days::new(ahead))
+                    .find(|next| mask & (1 << next.weekday().num_days_from_monday()) != 0)
+                    .expect("a non-empty weekday mask matches within a week")
+            }
+            Recurrence::Monthly(wanted) => {
+                let wanted = if wanted == 0 { day.day() } else { wanted.into() };
+                let this_month = on_day(day, wanted);
+                if this_month > day {
+                    this_month
+                } else {
+                    on_day(day.with_day(1).unwrap() + Months::new(1), wanted)
+                }
+            }
+        }
+    }
+}
+
+// `wanted` in the month of `day`, clamped to that month's length
+fn on_day(day: NaiveDate, wanted: u32) -> NaiveDate {
+    (1..=wanted.min(31))
+        .rev()
+        .find_map(|d| day.with_day(d))
+        .unwrap_or(day)
+}
+
+fn end_of_day() -> NaiveTime {
+    NaiveTime::from_hms_opt(END_OF_DAY.0, END_OF_DAY.1, 0).unwrap()
+}
+
+// A time skipped by a DST change moves an hour later, past the gap, like `dates::day_start`
+fn local(day: NaiveDate, time: NaiveTime) -> i64 {
+    let naive: NaiveDateTime = day.and_time(time);
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .or_else(|| Local.from_local_datetime(&(naive + Duration::hours(1))).earliest())
+        .unwrap_or_default()
+        .timestamp()
+}
+
+impl fmt::Display for Recurrence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Recurrence::Daily => f.write_str("daily"),
+            Recurrence::Weekly(0) => f.write_str("weekly"),
+            Recurrence::Weekly(mask) => {
+                let days: Vec<&str> = WEEKDAYS
+                    .iter()
+                    .filter(|(_, day)| mask & (1 << day.num_days_from_monday()) != 0)
+                    .map(|(name, _)| *name)
+                    .collect();
+                write!(f, "weekly:{}", days.join(","))
+            }
+            Recurrence::Monthly(0) => f.write_str("monthly"),
+            Recurrence::Monthly(day) => write!(f, "monthly:{}", day),
+            Recurrence::AfterDone(days) => write!(f, "after:{}d", days),
+        }
+    }
+}
+
+impl FromStr for Recurrence {
+    type Err = TodoError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || {
+            TodoError::InvalidInput(format!(
+                "cannot understand the repeat rule '{}', use daily, weekly[:mon,thu], monthly[:15] or after:<n>d",
+                s
+            ))
+        };
+        let lowered = s.to_lowercase();
+        let (kind, arg) = match lowered.split_once(':') {
+            Some((kind, arg)) => (kind, Some(arg)),
+            None => (lowered.as_str(), None),
+        };
+        match (kind, arg) {
+            ("daily", None) => Ok(Recurrence::Daily),
+            ("weekly", None) => Ok(Recurrence::Weekly(0)),
+            ("weekly", Some(days)) => {
+                let mut mask = 0;
+                for name in days.split(',') {
+                    let day = dates::parse_weekday(name.trim()).ok_or_else(invalid)?;
+                    mask |= 1 << day.num_days_from_monday();
+                }
+                Ok(Recurrence::Weekly(mask))
+            }
+            ("monthly", None) => Ok(Recurrence::Monthly(0)),
+            ("monthly", Some(day)) => match day.parse() {
+                Ok(day @ 1..=31) => Ok(Recurrence::Monthly(day)),
+                _ => Err(invalid()),
+            },
+            ("after", Some(days)) => match days.strip_suffix('d').map(str::parse) {
+                Some(Ok(days @ 1..)) => Ok(Recurrence::AfterDone(days)),
+                _ => Err(invalid()),
+            },
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl From<Recurrence> for String {
+    fn from(rule: Recurrence) -> Self {
+        rule.to_string()
+    }
+}
+
+impl TryFrom<String> for Recurrence {
+    type Error = TodoError;
+
+    fn try_from(text: String) -> Result<Self> {
+        text.parse()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dates::tests::at;
+
+    fn next(rule: &str, due: &str, completed: &str) -> DateTime<Local> {
+        let rule: Recurrence = rule.parse().unwrap();
+        let due = at(due);
+        dates::from_timestamp(rule.anchored(due).next_due(Some(due.timestamp()), at(completed)))
+    }
+
+    #[test]
+    fn monthly_clamps_to_short_months() {
+        assert_eq!(next("monthly:31", "2026-01-31 09:00", "2026-01-31 10:00"), at("2026-02-28 09:00"));
+        assert_eq!(next("monthly:31", "2026-02-28 09:00", "2026-02-28 10:00"), at("2026-03-31 09:00"));
+        assert_eq!(next("monthly", "2026-01-31 09:00", "2026-01-31 10:00"), at("2026-02-28 09:00"));
+    }
+
+    #[test]
+    fn weekly_steps_through_its_weekdays() {
+        // 2026-03-09 is a Monday
+        assert_eq!(next("weekly:mon,thu", "2026-03-09 09:00", "2026-03-09 10:00"), at("2026-03-12 09:00"));
+        assert_eq!(next("weekly:mon,thu", "2026-03-12 09:00", "2026-03-12 10:00"), at("2026-03-16 09:00"));
+        assert_eq!(next("weekly", "2026-03-12 09:00", "2026-03-12 10:00"), at("2026-03-19 09:00"));
+    }
+
+    #[test]
+    fn late_completions_skip_missed_occurrences() {
+        assert_eq!(next("daily", "2026-03-01 09:00", "2026-03-05 12:00"), at("2026-03-06 09:00"));
+        assert_eq!(next("weekly:mon", "2026-03-02 09:00", "2026-03-20 12:00"), at("2026-03-23 09:00"));
+        assert_eq!(next("daily", "2026-03-05 09:00", "2026-03-04 12:00"), at("2026-03-06 09:00"), "done early");
+    }
+
+    #[test]
+    fn after_counts_from_completion() {
+        assert_eq!(next("after:3d", "2026-03-01 09:00", "2026-03-05 12:00"), at("2026-03-08 09:00"));
+        assert_eq!(next("after:3d", "2026-03-10 09:00", "2026-03-05 12:00"), at("2026-03-08 09:00"));
+    }
+
+    #[test]
+    fn rules_read_back_as_written() {
+        for rule in ["daily", "weekly", "weekly:mon,thu", "monthly", "monthly:15", "after:3d"] {
+            assert_eq!(rule.parse::<Recurrence>().unwrap().to_string(), rule);
+        }
+        assert_eq!("weekly:Monday, thu".parse::<Recurrence>().unwrap().to_string(), "weekly:mon,thu");
+        for rule in ["hourly", "monthly:32", "after:0d", "weekly:someday"] {
+            assert!(rule.parse::<Recurrence>().is_err(), "{}", rule);
+        }
+    }
+
+    #[test]
+    fn near_misses_are_refused() {
+        for rule in ["weekly:monkey,thunder", "weekly:mo", "weekly:", "after:3ddd", "after:3", "after:d", "daily:2"] {
+            assert!(rule.parse::<Recurrence>().is_err(), "{}", rule);
+        }
+    }
+
+    #[test]
+    fn times_skipped_by_dst_move_past_the_gap() {
+        // 2026-03-29 has no 02:00 to 03:00 in Central European time
+        assert_eq!(next("daily", "2026-03-28 02:30", "2026-03-28 03:00"), at("2026-03-29 03:30"));
+        assert_eq!(next("daily", "2026-03-29 03:30", "2026-03-29 04:00"), at("2026-03-30 03:30"));
+    }
+}
@@ -0,0 +1,328 @@
+//! On-disk encoding of a `Todo`.
+//!
+//! Records start with a two byte header, `0xFF <version>`, followed by the bincode
+//! payload of that version. `0xFF` can never start a headerless record: those begin
+//! with the varint length of `thing`, and bincode reserves that byte.
+//!
+//! Records written before the header existed are told apart by trying each old
+//! layout, newest first, and keeping the one that consumes every byte.
+//!
+//! To change `Todo`: freeze its current shape as a new `vN` struct below, bump
+//! `CURRENT`, decode the frozen version in `decode` and add the upgrade step.
+
+use bincode::config::standard;
+use bincode::Decode;
+
+use crate::error::{Result, TodoError};
+use crate::{Prior, Recurrence, Todo};
+
+pub const CURRENT: u8 = 6;
+const MARKER: u8 = 0xFF;
+
+/// `thing`, `priority`, `done`: the original record.
+#[derive(Decode)]
+#[cfg_attr(test, derive(bincode::Encode))]
+struct V0 {
+    thing: String,
+    priority: Prior,
+    done: bool,
+}
+
+/// Adds due date, creation and completion timestamps.
+#[derive(Decode)]
+#[cfg_attr(test, derive(bincode::Encode))]
+struct V1 {
+    thing: String,
+    priority: Prior,
+    done: bool,
+    due: Option<i64>,
+    created: i64,
+    completed: Option<i64>,
+}
+
+/// Adds tags and project. Version 3 has the same layout, behind a header.
+#[derive(Decode)]
+#[cfg_attr(test, derive(bincode::Encode))]
+struct V2 {
+    thing: String,
+    priority: Prior,
+    done: bool,
+    due: Option<i64>,
+    created: i64,
+    completed: Option<i64>,
+    tags: Vec<String>,
+    project: Option<String>,
+}
+
+/// Adds parent and blocked_by.
+#[derive(Decode)]
+#[cfg_attr(test, derive(bincode::Encode))]
+struct V4 {
+    thing: String,
+    priority: Prior,
+    done: bool,
+    due: Option<i64>,
+    created: i64,
+    completed: Option<i64>,
+    tags: Vec<String>,
+    project: Option<String>,
+    parent: Option<u64>,
+    blocked_by: Vec<u64>,
+}
+
+/// Adds recur.
+#[derive(Decode)]
+#[cfg_attr(test, derive(bincode::Encode))]
+struct V5 {
+    thing: String,
+    priority: Prior,
+    done: bool,
+    due: Option<i64>,
+    created: i64,
+    completed: Option<i64>,
+    tags: Vec<String>,
+    project: Option<String>,
+    parent: Option<u64>,
+    blocked_by: Vec<u64>,
+    recur: Option<Recurrence>,
+}
+
+impl From<V0> for V1 {
+    fn from(old: V0) -> Self {
+        V1 {
+            thing: old.thing,
+            priority: old.priority,
+            done: old.done,
+            due: None,
+            created: 0, // unknown
+            completed: None,
+        }
+    }
+}
+
+impl From<V1> for V2 {
+    fn from(old: V1) -> Self {
+        V2 {
+            thing: old.thing,
+            priority: old.priority,
+            done: old.done,
+            due: old.due,
+            created: old.created,
+            completed: old.completed,
+            tags: Vec::new(),
+            project: None,
+        }
+    }
+}
+
+impl From<V2> for V4 {
+    fn from(old: V2) -> Self {
+        V4 {
+            thing: old.thing,
+            priority: old.priority,
+            done: old.done,
+            due: old.due,
+            created: old.created,
+            completed: old.completed,
+            tags: old.tags,
+            project: old.project,
+            parent: None,
+            blocked_by: Vec::new(),
+        }
+    }
+}
+
+impl From<V4> for V5 {
+    fn from(old: V4) -> Self {
+        V5 {
+            thing: old.thing,
+            priority: old.priority,
+            done: old.done,
+            due: old.due,
+            created: old.created,
+            completed: old.completed,
+            tags: old.tags,
+            project: old.project,
+            parent: old.parent,
+            blocked_by: old.blocked_by,
+            recur: None,
+        }
+    }
+}
+
+impl From<V5> for Todo {
+    fn from(old: V5) -> Self {
+        Todo {
+            thing: old.thing,
+            priority: old.priority,
+            done: old.done,
+            due: old.due,
+            created: old.created,
+            completed: old.completed,
+            tags: old.tags,
+            project: old.project,
+            parent: old.parent,
+            blocked_by: old.blocked_by,
+            recur: old.recur,
+            notes: None,
+        }
+    }
+}
+
+pub fn encode(todo: &Todo) -> Vec<u8> {
+    let mut bytes = vec![MARKER, CURRENT];
+    // Every field of `Todo` is encodable and a Vec grows as needed, so this cannot fail
+    bincode::encode_into_std_write(todo, &mut bytes, standard()).expect("encoding a Todo into memory");
+    bytes
+}
+
+/// Decodes a record of any known version, upgraded to the current `Todo`.
+pub fn decode(bytes: &[u8]) -> Result<Todo> {
+    match version(bytes) {
+        Some(CURRENT) => exact::<Todo>(&bytes[2..]).ok_or_else(|| TodoError::decode("corrupt record")),
+        Some(5) => exact::<V5>(&bytes[2..])
+            .map(Todo::from)
+            .ok_or_else(|| TodoError::decode("corrupt record")),
+        Some(4) => exact::<V4>(&bytes[2..])
+            .map(|v4| V5::from(v4).into())
+            .ok_or_else(|| TodoError::decode("corrupt record")),
+        Some(3) => exact::<V2>(&bytes[2..])
+            .map(|v2| V5::from(V4::from(v2)).into())
+            .ok_or_else(|| TodoError::decode("corrupt record")),
+        Some(newer) if newer > CURRENT => Err(TodoError::decode(format!(
+            "record version {} is newer than this program",
+            newer
+        ))),
+        Some(other) => Err(TodoError::decode(format!("unknown record version {}", other))),
+        None => decode_headerless(bytes),
+    }
+}
+
+/// The header version, or `None` for records written before headers existed.
+pub fn version(bytes: &[u8]) -> Option<u8> {
+    match bytes {
+        [MARKER, version, ..] => Some(*version),
+        _ => None,
+    }
+}
+
+fn decode_headerless(bytes: &[u8]) -> Result<Todo> {
+    if let Some(v2) = exact::<V2>(bytes) {
+        return Ok(V5::from(V4::from(v2)).into());
+    }
+    if let Some(v1) = exact::<V1>(bytes) {
+        return Ok(V5::from(V4::from(V2::from(v1))).into());
+    }
+    if let Some(v0) = exact::<V0>(bytes) {
+        return Ok(V5::from(V4::from(V2::from(V1::from(v0)))).into());
+    }
+    Err(TodoError::decode("record matches no known version"))
+}
+
+// Decodes `T` only when it uses every byte, which is what tells the old layouts apart
+fn exact<T: Decode<()>>(bytes: &[u8]) -> Option<T> {
+    match bincode::decode_from_slice::<T, _>(bytes, standard()) {
+        Ok((value, read)) if read == bytes.len() => Some(value),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stored<T: bincode::Encode>(version: Option<u8>, record: T) -> Vec<u8> {
+        let header = version.map_or(Vec::new(), |version| vec![MARKER, version]);
+        [header, bincode::encode_to_vec(record, standard()).unwrap()].concat()
+    }
+
+    fn v2() -> V2 {
+        V2 {
+            thing: "renew passport".into(),
+            priority: Prior::High,
+            done: true,
+            due: Some(1_700_000_000),
+            created: 1_690_000_000,
+            completed: Some(1_695_000_000),
+            tags: vec!["admin".into()],
+            project: Some("travel".into()),
+        }
+    }
+
+    fn upgraded() -> Todo {
+        Todo {
+            created: 1_690_000_000,
+            ..Todo::new("renew passport".into(), Prior::High)
+        }
+    }
+
+    #[test]
+    fn headerless_records_upgrade() {
+        let v0 = V0 {
+            thing: "renew passport".into(),
+            priority: Prior::High,
+            done: true,
+        };
+        let expected = Todo {
+            done: true,
+            created: 0,
+            ..upgraded()
+        };
+        assert_eq!(decode(&stored(None, v0)).unwrap(), expected);
+
+        let v1 = V1 {
+            thing: "renew passport".into(),
+            priority: Prior::High,
+            done: true,
+            due: Some(1_700_000_000),
+            created: 1_690_000_000,
+            completed: Some(1_695_000_000),
+        };
+        let expected = Todo {
+            done: true,
+            due: Some(1_700_000_000),
+            completed: Some(1_695_000_000),
+            ..upgraded()
+        };
+        assert_eq!(decode(&stored(None, v1)).unwrap(), expected);
+
+        let expected = Todo {
+            tags: vec!["admin".into()],
+            project: Some("travel".into()),
+            ..expected
+        };
+        assert_eq!(decode(&stored(None, v2())).unwrap(), expected);
+        assert_eq!(decode(&stored(Some(3), v2())).unwrap(), expected, "version 3 is V2 behind a header");
+    }
+
+    #[test]
+    fn headed_records_upgrade() {
+        let v4 = V4::from(v2());
+        let v5 = V5 {
+            recur: Some(Recurrence::Daily),
+            ..V5::from(V4 {
+                parent: Some(7),
+                blocked_by: vec![8],
+                ..V4::from(v2())
+            })
+        };
+        assert_eq!(decode(&stored(Some(4), v4)).unwrap(), V5::from(V4::from(v2())).into());
+        let todo = decode(&stored(Some(5), v5)).unwrap();
+        assert_eq!((todo.parent, &todo.blocked_by[..], todo.recur), (Some(7), &[8][..], Some(Recurrence::Daily)));
+        assert_eq!(todo.notes, None);
+
+        let current = Todo {
+            notes: Some("bring photos".into()),
+            ..todo
+        };
+        assert_eq!(version(&encode(&current)), Some(CURRENT));
+        assert_eq!(decode(&encode(&current)).unwrap(), current);
+    }
+
+    #[test]
+    fn unknown_records_are_refused() {
+        assert!(matches!(decode(&stored(Some(CURRENT + 1), v2())), Err(TodoError::Decode { .. })));
+        assert!(matches!(decode(&stored(Some(2), v2())), Err(TodoError::Decode { .. })));
+        assert!(matches!(decode(&[1, 2, 3]), Err(TodoError::Decode { .. })));
+    }
+}
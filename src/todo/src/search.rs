@@ -0,0 +1,180 @@
+//! Full-text search over `thing` and `notes`.
+//!
+//! Text splits into words at anything that is not a letter or digit, lowercased.
+//! The `by_word` tree holds one key per word of every todo,
+//! `<word> 0x00 <id> <count in thing> <count in notes>`, so postings and their
+//! weights come from the keys alone, and the distinct words can be walked by
+//! seeking past each one for the fuzzy matches.
+//!
+//! A query word matches a whole word, the start of a longer one, or, from four
+//! letters on, a word a typo away (two typos from eight letters). A todo has to
+//! match every query word. Its score adds up, per query word, the best match's
+//! quality times its weight: whole words beat prefixes beat typos, and the thing
+//! counts double the notes.
+//!
+//! An encrypted store keeps no `by_word` tree, since it would give the words away,
+//! and builds the same keys in memory from the decrypted todos for each search.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use crate::error::{Result, TodoError};
+use crate::Todo;
+
+const MAX_WORD: usize = 64; // longer words are cut, keeping keys short
+const SUFFIX: usize = 11; // the 0x00, the ID and the two counts
+
+/// The lowercase words of `text`.
+pub fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase().chars().take(MAX_WORD).collect())
+}
+
+/// The `by_word` keys of one todo.
+pub(crate) fn entries(id: u64, todo: &Todo) -> Vec<Vec<u8>> {
+    let mut counts: BTreeMap<String, [u8; 2]> = BTreeMap::new();
+    for (field, text) in [(0, Some(&todo.thing)), (1, todo.notes.as_ref())] {
+        for word in text.into_iter().flat_map(|text| words(text)) {
+            let count = &mut counts.entry(word).or_default()[field];
+            *count = count.saturating_add(1);
+        }
+    }
+    counts
+        .into_iter()
+        .map(|(word, [thing, notes])| [word.as_bytes(), &[0], &id.to_be_bytes(), &[thing, notes]].concat())
+        .collect()
+}
+
+struct Posting<'a> {
+    word: &'a str,
+    id: u64,
+    thing: u8,
+    notes: u8,
+}
+
+fn posting(key: &[u8]) -> Result<Posting<'_>> {
+    let corrupt = || TodoError::decode("corrupt word index key");
+    let split = key.len().checked_sub(SUFFIX).ok_or_else(corrupt)?;
+    let (word, suffix) = key.split_at(split);
+    if suffix[0] != 0 {
+        return Err(corrupt());
+    }
+    Ok(Posting {
+        word: std::str::from_utf8(word).map_err(|_| corrupt())?,
+        id: u64::from_be_bytes(suffix[1..9].try_into().unwrap()),
+        thing: suffix[9],
+        notes: suffix[10],
+    })
+}
+
+/// Where [`rank`] reads `by_word` keys from.
+pub(crate) trait Postings {
+    /// Keys starting with `prefix`, in order.
+    fn scan(&self, prefix: &[u8]) -> Result<Vec<Vec<u8>>>;
+    /// The first key at or after `from`.
+    fn first_from(&self, from: &[u8]) -> Result<Option<Vec<u8>>>;
+}
+
+impl Postings for sled::Tree {
+    fn scan(&self, prefix: &[u8]) -> Result<Vec<Vec<u8>>> {
+        self.scan_prefix(prefix).keys().map(|key| Ok(key?.to_vec())).collect()
+    }
+
+    fn first_from(&self, from: &[u8]) -> Result<Option<Vec<u8>>> {
+        Ok(self.range(from..).keys().next().transpose()?.map(|key| key.to_vec()))
+    }
+}
+
+impl Postings for BTreeSet<Vec<u8>> {
+    fn scan(&self, prefix: &[u8]) -> Result<Vec<Vec<u8>>> {
+        Ok(self
+            .range(prefix.to_vec()..)
+            .take_while(|key| key.starts_with(prefix))
+            .cloned()
+            .collect())
+    }
+
+    fn first_from(&self, from: &[u8]) -> Result<Option<Vec<u8>>> {
+        Ok(self.range(from.to_vec()..).next().cloned())
+    }
+}
+
+/// IDs matching every word of `query` with their scores, best first.
+pub(crate) fn rank(postings: &impl Postings, query: &str) -> Result<Vec<(u64, f64)>> {
+    let mut terms: Vec<String> = words(query).collect();
+    terms.sort();
+    terms.dedup();
+    if terms.is_empty() {
+        return Err(TodoError::InvalidInput("nothing to search for".into()));
+    }
+    let vocabulary = if terms.iter().any(|term| typos(term) > 0) {
+        vocabulary(postings)?
+    } else {
+        Vec::new()
+    };
+
+    let mut scores: Option<HashMap<u64, f64>> = None;
+    for term in &terms {
+        let mut best: HashMap<u64, f64> = HashMap::new();
+        let mut note = |key: &[u8], quality: f64| -> Result<()> {
+            let posting = posting(key)?;
+            let weight = 2.0 * f64::from(posting.thing.min(3)) + f64::from(posting.notes.min(3));
+            let score = best.entry(posting.id).or_default();
+            *score = score.max(quality * weight);
+            Ok(())
+        };
+        // Whole words and longer words starting with the term sort together
+        for key in postings.scan(term.as_bytes())? {
+            let word = posting(&key)?.word;
+            let quality = if word == term {
+                1.0
+            } else {
+                0.5 + 0.4 * term.len() as f64 / word.len() as f64
+            };
+            note(&key, quality)?;
+        }
+        for word in &vocabulary {
+            if word.starts_with(term.as_str()) {
+                continue;
+            }
+            let distance = strsim::osa_distance(term, word);
+            if distance <= typos(term) {
+                let quality = 0.4 / distance as f64;
+                for key in postings.scan(&[word.as_bytes(), &[0]].concat())? {
+                    note(&key, quality)?;
+                }
+            }
+        }
+        scores = Some(match scores {
+            None => best,
+            Some(so_far) => so_far
+                .into_iter()
+                .filter_map(|(id, score)| best.get(&id).map(|more| (id, score + more)))
+                .collect(),
+        });
+    }
+
+    let mut ranked: Vec<(u64, f64)> = scores.unwrap_or_default().into_iter().collect();
+    ranked.sort_by(|(a, a_score), (b, b_score)| b_score.total_cmp(a_score).then(a.cmp(b)));
+    Ok(ranked)
+}
+
+fn typos(term: &str) -> usize {
+    match term.chars().count() {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    }
+}
+
+// Every distinct word, found by seeking past the keys of each one in turn
+fn vocabulary(postings: &impl Postings) -> Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut from = Vec::new();
+    while let Some(key) = postings.first_from(&from)? {
+        let word = posting(&key)?.word.to_string();
+        from = [word.as_bytes(), &[1]].concat();
+        words.push(word);
+    }
+    Ok(words)
+}
@@ -0,0 +1,937 @@
+use chrono::Local;
+use sled::transaction::{ConflictableTransactionError, TransactionalTree, UnabortableTransactionError};
+use sled::{Db, Transactional};
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeSet, HashSet};
+use std::path::Path;
+use zeroize::Zeroizing;
+
+use crate::crypto::{self, Cipher};
+use crate::error::{Result, TodoError};
+use crate::history::{self, Change, Event, EventKind};
+use crate::index::{self, Index, Trees};
+use crate::query::Query;
+use crate::search;
+use crate::sync::{self, Record, Seen, Stamp, Uid};
+use crate::timelog::{self, Interval, TIMER};
+use crate::{dates, schema, Todo};
+
+/// A todo list stored in a sled database. Records live in the `todos` tree keyed by
+/// big-endian ID, so iteration is in ID order; tags, projects, due dates and parents
+/// are indexed in separate trees that every write keeps in step.
+///
+/// A todo can be a subtask of a `parent` and be `blocked_by` other todos. Writes
+/// that would make either relation loop are rejected. Removing a todo leaves
+/// links to it in place; a missing parent or blocker reads as done.
+///
+/// Every transaction that changes something is logged as one [`Event`], which
+/// [`undo`](Self::undo) reverts as a whole.
+///
+/// Writes also stamp the todo's [`Record`], which is what [`sync`](crate::sync)
+/// swaps with other devices.
+///
+/// An encrypted database seals every todo, event and sync record and keeps no word
+/// index; tags, projects, due dates and links stay readable in the index keys, and
+/// the [`timelog`](crate::timelog) stays readable as a whole.
+///
+/// ```no_run
+/// use todo::{Prior, Todo, TodoStore};
+///
+/// let store = TodoStore::open("/tmp/todo-db")?;
+/// let id = store.insert(&Todo::new("water the plants".into(), Prior::Soft))?;
+/// store.transaction(|tx| {
+///     let mut todo = tx.get(id)?;
+///     todo.done = true;
+///     tx.update(id, &todo)
+/// })?;
+/// # Ok::<(), todo::TodoError>(())
+/// ```
+pub struct TodoStore {
+    db: Db,
+    trees: Trees,
+    cipher: Option<Cipher>,
+}
+
+/// Error inside a [`TodoStore::transaction`] closure. Both sled conflicts, which make
+/// the transaction retry, and [`TodoError`]s, which abort it, convert into it with `?`.
+pub struct TxError(ConflictableTransactionError<TodoError>);
+
+pub type TxResult<T> = std::result::Result<T, TxError>;
+
+impl From<TodoError> for TxError {
+    fn from(e: TodoError) -> Self {
+        TxError(ConflictableTransactionError::Abort(e))
+    }
+}
+
+impl From<UnabortableTransactionError> for TxError {
+    fn from(e: UnabortableTransactionError) -> Self {
+        TxError(e.into())
+    }
+}
+
+impl TodoStore {
+    /// Opens an unencrypted database; an encrypted one fails with [`TodoError::Passphrase`].
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::open_with(path, || {
+            Err(TodoError::Passphrase("the database is encrypted and no passphrase was given".into()))
+        })
+    }
+
+    /// Opens a database that may be encrypted, calling `passphrase` only if it is.
+    pub fn open_with(
+        path: impl AsRef<Path>,
+        passphrase: impl FnOnce() -> Result<Zeroizing<String>>,
+    ) -> Result<Self> {
+        let path = path.as_ref();
+        let db = sled::open(path).map_err(|e| match e {
+            // sled reports a held lock as a plain I/O error
+            sled::Error::Io(ref io) if io.to_string().contains("could not acquire lock") => {
+                TodoError::Locked(path.to_path_buf())
+            }
+            other => other.into(),
+        })?;
+        let cipher = match db.open_tree("meta")?.get("crypt")? {
+            Some(header) => Some(Cipher::unlock(&header, &passphrase()?)?),
+            None => None,
+        };
+        let trees = Trees::open(&db, cipher.as_ref())?;
+        Ok(TodoStore { db, trees, cipher })
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.cipher.is_some()
+    }
+
+    /// Encrypts the database under `passphrase`, moves it to a new one, or with `None`
+    /// stores it in the clear again. Every sealed value is rewritten in one
+    /// transaction. Until sled compacts its log, the old bytes can linger on disk.
+    pub fn set_passphrase(&mut self, passphrase: Option<&str>) -> Result<()> {
+        let (cipher, header) = match passphrase {
+            Some(passphrase) => {
+                let (cipher, header) = Cipher::create(passphrase)?;
+                (Some(cipher), Some(header))
+            }
+            None => (None, None),
+        };
+        let t = &self.trees;
+        let mut values = Vec::new();
+        for (name, tree) in [("todos", &t.todos), ("events", &t.events), ("sync", &t.sync)] {
+            for entry in tree.iter() {
+                let (key, value) = entry?;
+                let plain = crypto::open(self.cipher.as_ref(), name, &key, &value)?;
+                values.push((name, key, plain));
+            }
+        }
+        let result = (&t.todos, &t.events, &t.sync, &t.meta).transaction(|(todos, events, sync, meta)| {
+            for (name, key, plain) in &values {
+                let tree = match *name {
+                    "todos" => todos,
+                    "events" => events,
+                    _ => sync,
+                };
+                tree.insert(key, crypto::seal(cipher.as_ref(), name, key, plain))?;
+            }
+            match &header {
+                Some(header) => meta.insert("crypt", header.as_slice())?,
+                None => meta.remove("crypt")?,
+            };
+            Ok::<_, ConflictableTransactionError<TodoError>>(())
+        });
+        result?;
+        self.cipher = cipher;
+        self.trees.rebuild(self.cipher.as_ref())?; // drops or restores the word index
+        self.flush()
+    }
+
+    /// Stores a new todo under the next free ID and returns that ID.
+    pub fn insert(&self, todo: &Todo) -> Result<u64> {
+        self.transaction(|tx| tx.insert(todo))
+    }
+
+    pub fn get(&self, id: u64) -> Result<Todo> {
+        match self.trees.todos.get(id.to_be_bytes())? {
+            Some(bytes) => read_todo(self.cipher.as_ref(), id, &bytes),
+            None => Err(TodoError::NotFound(id)),
+        }
+    }
+
+    pub fn update(&self, id: u64, todo: &Todo) -> Result<()> {
+        self.transaction(|tx| tx.update(id, todo))
+    }
+
+    /// Removes a todo and returns it.
+    pub fn delete(&self, id: u64) -> Result<Todo> {
+        self.transaction(|tx| tx.delete(id))
+    }
+
+    /// Every todo in ID order.
+    pub fn iter(&self) -> impl Iterator<Item = Result<(u64, Todo)>> + '_ {
+        self.trees.todos.iter().map(|entry| {
+            let (key, value) = entry?;
+            let id = key_to_id(&key)?;
+            Ok((id, read_todo(self.cipher.as_ref(), id, &value)?))
+        })
+    }
+
+    /// Direct subtasks of `id`, in ID order.
+    pub fn children(&self, id: u64) -> Result<Vec<(u64, Todo)>> {
+        self.trees
+            .children(id)?
+            .into_iter()
+            .map(|child| Ok((child, self.get(child)?)))
+            .collect()
+    }
+
+    /// The blockers of `todo` that are not done yet.
+    pub fn open_blockers(&self, todo: &Todo) -> Result<Vec<u64>> {
+        let mut open = Vec::new();
+        for &blocker in &todo.blocked_by {
+            match self.get(blocker) {
+                Ok(other) if !other.done => open.push(blocker),
+                Ok(_) | Err(TodoError::NotFound(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(open)
+    }
+
+    /// Todos matching `query`, in ID order. Indexed terms narrow the records that
+    /// get decoded at all.
+    pub fn query(&self, query: &Query) -> Result<Vec<(u64, Todo)>> {
+        let mut items = match self.trees.candidates(query)? {
+            Some(ids) => ids
+                .into_iter()
+                .map(|id| Ok((id, self.get(id)?)))
+                .collect::<Result<Vec<_>>>()?,
+            None => self.iter().collect::<Result<Vec<_>>>()?,
+        };
+        items.retain(|(_, todo)| query.matches(todo));
+        Ok(items)
+    }
+
+    /// Runs `f` atomically across the records, their indexes and the event log, so
+    /// either every change it makes lands, as one event, or none does. sled may run
+    /// it more than once when it conflicts with a concurrent writer, so `f` must not
+    /// have side effects outside the transaction.
+    pub fn transaction<T>(&self, f: impl Fn(&Transaction) -> TxResult<T>) -> Result<T> {
+        self.run(true, f)
+    }
+
+    /// Todos matching every word of `query` in their thing or notes, best match
+    /// first, then open before done. See [`search`](crate::search) for the rules.
+    pub fn search(&self, query: &str) -> Result<Vec<(u64, Todo)>> {
+        let ranked = match self.cipher {
+            None => search::rank(&self.trees.words, query)?,
+            Some(_) => {
+                let mut postings = BTreeSet::new();
+                for item in self.iter() {
+                    let (id, todo) = item?;
+                    postings.extend(search::entries(id, &todo));
+                }
+                search::rank(&postings, query)?
+            }
+        };
+        let mut hits = ranked
+            .into_iter()
+            .map(|(id, score)| Ok((score, self.get(id)?, id)))
+            .collect::<Result<Vec<_>>>()?;
+        hits.sort_by(|(a_score, a, a_id), (b_score, b, b_id)| {
+            b_score.total_cmp(a_score).then(a.done.cmp(&b.done)).then(a_id.cmp(b_id))
+        });
+        Ok(hits.into_iter().map(|(_, todo, id)| (id, todo)).collect())
+    }
+
+    /// Starts timing `id` from `at`. Fails while a timer runs, whichever todo it is on.
+    pub fn start(&self, id: u64, at: i64) -> Result<()> {
+        self.run(false, |tx| {
+            if let Some(key) = tx.meta.get(TIMER)? {
+                let running = timelog::decode(&key, &[])?;
+                return Err(TodoError::InvalidInput(format!(
+                    "the timer is already running on #{}, stop it first",
+                    running.id
+                ))
+                .into());
+            }
+            if tx.get(id)?.done {
+                return Err(TodoError::InvalidInput(format!("#{} is done, reopen it first", id)).into());
+            }
+            let key = timelog::key(id, at);
+            tx.time.insert(key.as_slice(), &[])?;
+            tx.meta.insert(TIMER, key)?;
+            Ok(())
+        })
+    }
+
+    /// Stops the running timer at `at` and returns the interval it logged, or `None`
+    /// when no timer was running. Completing or removing the timed todo stops it too.
+    pub fn stop(&self, at: i64) -> Result<Option<Interval>> {
+        self.run(false, |tx| tx.stop_timer(None, at))
+    }
+
+    /// The interval of the running timer, if any.
+    pub fn running(&self) -> Result<Option<Interval>> {
+        self.trees
+            .meta
+            .get(TIMER)?
+            .map(|key| timelog::decode(&key, &[]))
+            .transpose()
+    }
+
+    /// Logged intervals that overlap `from..to`, the running one included, by start.
+    pub fn intervals(&self, from: i64, to: i64) -> Result<Vec<Interval>> {
+        let mut intervals = Vec::new();
+        for entry in self.trees.time.range(..index::sortable(to).to_vec()) {
+            let (key, value) = entry?;
+            let interval = timelog::decode(&key, &value)?;
+            if interval.end.is_none_or(|end| end > from) {
+                intervals.push(interval);
+            }
+        }
+        Ok(intervals)
+    }
+
+    /// Reverts the latest event not undone yet and returns it, or `None` when there
+    /// is nothing to undo. Fails without changing anything if one of its todos was
+    /// changed since by something the log does not know about.
+    pub fn undo(&self) -> Result<Option<Event>> {
+        self.run(true, |tx| tx.replay(UNDO, |change| (&change.after, &change.before), EventKind::Undo))
+    }
+
+    /// Applies the latest undone event again, as long as nothing was logged since.
+    pub fn redo(&self) -> Result<Option<Event>> {
+        self.run(true, |tx| tx.replay(REDO, |change| (&change.before, &change.after), EventKind::Redo))
+    }
+
+    /// Logged events, newest first.
+    pub fn history(&self) -> impl Iterator<Item = Result<Event>> + '_ {
+        self.trees.events.iter().rev().map(|entry| {
+            let (key, value) = entry?;
+            history::decode(key_to_id(&key)?, &crypto::open(self.cipher.as_ref(), "events", &key, &value)?)
+        })
+    }
+
+    /// This device's version vector: the highest clock it holds from each device.
+    pub fn seen(&self) -> Result<Seen> {
+        let mut seen = match self.trees.meta.get("seen")? {
+            Some(bytes) => sync::decode_seen(&bytes)?,
+            None => Seen::new(),
+        };
+        if let (Some(device), Some(clock)) = (self.trees.meta.get("device")?, self.trees.meta.get("clock")?) {
+            seen.insert(key_to_id(&device)?, key_to_id(&clock)?); // every stamp made here is kept
+        }
+        Ok(seen)
+    }
+
+    /// The records with a change that `seen` does not cover, removed todos included.
+    pub fn changes_since(&self, seen: &Seen) -> Result<Vec<Record>> {
+        self.track_all()?;
+        let mut records = Vec::new();
+        for entry in self.trees.sync.iter() {
+            let (key, value) = entry?;
+            let record = sync::decode(&crypto::open(self.cipher.as_ref(), "sync", &key, &value)?)?;
+            if record.newer_than(seen) {
+                records.push(record);
+            }
+        }
+        Ok(records)
+    }
+
+    /// Merges records from a peer that has seen `seen`, as one event, and returns how
+    /// many todos changed.
+    pub fn merge(&self, records: &[Record], seen: &Seen) -> Result<usize> {
+        self.track_all()?;
+        self.run(true, |tx| tx.merge(records, seen))
+    }
+
+    // Todos written before syncing existed have no record yet
+    fn track_all(&self) -> Result<()> {
+        let mut untracked = Vec::new();
+        for key in self.trees.todos.iter().keys() {
+            let key = key?;
+            if !self.trees.sync.contains_key(&key)? {
+                untracked.push(key_to_id(&key)?);
+            }
+        }
+        if untracked.is_empty() {
+            return Ok(());
+        }
+        self.run(false, |tx| {
+            for &id in &untracked {
+                if let Some(todo) = tx.find(id)? {
+                    tx.track(id, None, Some(&todo))?;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    // `log` is false for rewrites that change no todo, like migrations
+    fn run<T>(&self, log: bool, f: impl Fn(&Transaction) -> TxResult<T>) -> Result<T> {
+        let t = &self.trees;
+        let trees = (
+            &t.todos, &t.tags, &t.projects, &t.due, &t.children, &t.words, &t.events, &t.sync, &t.uids, &t.meta, &t.time,
+        );
+        let result = trees.transaction(
+            |(todos, tags, projects, due, children, words, events, sync, uids, meta, time)| {
+                let tx = Transaction {
+                    todos,
+                    tags,
+                    projects,
+                    due,
+                    children,
+                    words,
+                    events,
+                    sync,
+                    uids,
+                    meta,
+                    time,
+                    cipher: self.cipher.as_ref(),
+                    changes: RefCell::new(Vec::new()),
+                    kind: Cell::new(EventKind::Change),
+                };
+                let value = f(&tx).map_err(|TxError(e)| e)?;
+                if log {
+                    tx.log().map_err(|TxError(e)| e)?;
+                }
+                Ok(value)
+            },
+        );
+        Ok(result?)
+    }
+
+    /// Rewrites records stored by older versions in the current format. Older
+    /// records are readable as they are; this only saves decoding them the slow way.
+    pub fn migrate(&self) -> Result<usize> {
+        let mut upgraded = 0;
+        for entry in self.trees.todos.iter() {
+            let (key, value) = entry?;
+            let id = key_to_id(&key)?;
+            let value = crypto::open(self.cipher.as_ref(), "todos", &key, &value).map_err(|e| e.in_record(id))?;
+            if schema::version(&value) == Some(schema::CURRENT) {
+                continue;
+            }
+            let todo = schema::decode(&value).map_err(|e| e.in_record(id))?;
+            self.run(false, |tx| tx.update(id, &todo))?;
+            upgraded += 1;
+        }
+        Ok(upgraded)
+    }
+
+    pub fn flush(&self) -> Result<()> {
+        self.db.flush()?;
+        Ok(())
+    }
+}
+
+/// The store as seen from inside [`TodoStore::transaction`].
+pub struct Transaction<'a> {
+    todos: &'a TransactionalTree,
+    tags: &'a TransactionalTree,
+    projects: &'a TransactionalTree,
+    due: &'a TransactionalTree,
+    children: &'a TransactionalTree,
+    words: &'a TransactionalTree,
+    events: &'a TransactionalTree,
+    sync: &'a TransactionalTree,
+    uids: &'a TransactionalTree,
+    meta: &'a TransactionalTree,
+    time: &'a TransactionalTree,
+    cipher: Option<&'a Cipher>,
+    changes: RefCell<Vec<Change>>,
+    kind: Cell<EventKind>,
+}
+
+// `meta` keys of the undo and redo stacks, see `history::push`
+const UNDO: &str = "undo";
+const REDO: &str = "redo";
+const UNDO_LIMIT: usize = 1000;
+// `meta` key of the oldest event still in the log
+const FIRST_EVENT: &str = "first_event";
+
+impl Transaction<'_> {
+    pub fn get(&self, id: u64) -> TxResult<Todo> {
+        match self.todos.get(id.to_be_bytes())? {
+            Some(bytes) => Ok(read_todo(self.cipher, id, &bytes)?),
+            None => Err(TodoError::NotFound(id).into()),
+        }
+    }
+
+    /// IDs come from a counter in the `meta` tree, so removed IDs are never handed out again.
+    pub fn insert(&self, todo: &Todo) -> TxResult<u64> {
+        let id = self.next("next_id")?;
+        self.check_links(id, todo, None)?;
+        self.write(id, Some(todo))?;
+        Ok(id)
+    }
+
+    pub fn update(&self, id: u64, todo: &Todo) -> TxResult<()> {
+        let previous = self.get(id)?;
+        self.check_links(id, todo, Some(&previous))?;
+        self.write(id, Some(todo))?;
+        Ok(())
+    }
+
+    /// Marks `id` done. A recurring todo passes its rule on to a new open copy due at
+    /// the next occurrence, added in the same transaction; its ID is returned.
+    pub fn complete(&self, id: u64) -> TxResult<Option<u64>> {
+        let mut todo = self.get(id)?;
+        if todo.done {
+            return Ok(None);
+        }
+        todo.set_done(true);
+        let completed = dates::from_timestamp(todo.completed.unwrap_or_default());
+        let next = todo.recur.take().map(|rule| Todo {
+            done: false,
+            due: Some(rule.next_due(todo.due, completed)),
+            created: completed.timestamp(),
+            completed: None,
+            recur: Some(rule),
+            ..todo.clone()
+        });
+        self.update(id, &todo)?;
+        let next = next.map(|next| self.insert(&next)).transpose()?;
+        match next {
+            Some(next) => self.meta.insert(spawned_key(id), &next.to_be_bytes())?,
+            None => self.meta.remove(spawned_key(id))?,
+        };
+        Ok(next)
+    }
+
+    /// Marks `id` open again. If completing it added the next occurrence of a
+    /// recurring todo, that occurrence is removed and `id` takes the rule back;
+    /// once the occurrence is done as well, `id` cannot be reopened.
+    pub fn reopen(&self, id: u64) -> TxResult<()> {
+        let mut todo = self.get(id)?;
+        if !todo.done {
+            return Ok(());
+        }
+        if let Some(next) = self.meta.get(spawned_key(id))? {
+            let next = key_to_id(&next)?;
+            match self.find(next)? {
+                Some(occurrence) if occurrence.done => {
+                    return Err(TodoError::InvalidInput(format!(
+                        "#{} repeats as #{}, which is done too, so reopen that one instead",
+                        id, next
+                    ))
+                    .into());
+                }
+                Some(occurrence) => {
+                    self.delete(next)?;
+                    todo.recur = occurrence.recur;
+                }
+                None => {}
+            }
+        }
+        todo.set_done(false);
+        self.update(id, &todo)
+    }
+
+    pub fn delete(&self, id: u64) -> TxResult<Todo> {
+        self.write(id, None)?.ok_or_else(|| TodoError::NotFound(id).into())
+    }
+
+    // Bumps the counter at `key` in `meta` and returns its new value
+    fn next(&self, key: &str) -> TxResult<u64> {
+        let next = self.counter(key)? + 1;
+        self.meta.insert(key, &next.to_be_bytes())?;
+        Ok(next)
+    }
+
+    fn counter(&self, key: &str) -> TxResult<u64> {
+        match self.meta.get(key)? {
+            Some(bytes) => Ok(key_to_id(&bytes)?),
+            None => Ok(0),
+        }
+    }
+
+    // Stores what this transaction changed as the next event and updates the stacks:
+    // a new change can no longer be redone past, an undo can be redone and a redo undone
+    fn log(&self) -> TxResult<()> {
+        let changes = self.changes.take();
+        if changes.is_empty() {
+            return Ok(());
+        }
+        let seq = self.next("next_event")?;
+        let kind = self.kind.get();
+        let key = seq.to_be_bytes();
+        let event = history::encode(Local::now().timestamp(), kind, &changes);
+        self.events.insert(&key, crypto::seal(self.cipher, "events", &key, &event))?;
+        self.trim_events(seq)?;
+        match kind {
+            EventKind::Change | EventKind::Sync => {
+                self.push(UNDO, seq)?;
+                self.meta.remove(REDO)?;
+            }
+            EventKind::Undo(of) => self.push(REDO, of)?,
+            EventKind::Redo(of) => self.push(UNDO, of)?,
+        }
+        Ok(())
+    }
+
+    // Sequence numbers have no gaps, so the events to drop are known without
+    // iterating; a log older than the limit loses its excess on its next event
+    fn trim_events(&self, newest: u64) -> TxResult<()> {
+        let keep_from = newest.saturating_sub(history::EVENT_LIMIT) + 1;
+        let mut oldest = self.counter(FIRST_EVENT)?.max(1);
+        while oldest < keep_from {
+            self.events.remove(&oldest.to_be_bytes())?;
+            oldest += 1;
+        }
+        self.meta.insert(FIRST_EVENT, &oldest.to_be_bytes())?;
+        Ok(())
+    }
+
+    fn push(&self, stack: &str, seq: u64) -> TxResult<()> {
+        let bytes = self.meta.get(stack)?;
+        self.meta.insert(stack, history::push(bytes.as_deref(), seq, UNDO_LIMIT))?;
+        Ok(())
+    }
+
+    // Pops the newest event off `from`, checks each todo it touched is still in the
+    // state `sides` calls expected, and puts back the other side. Undo and redo only
+    // differ in the stacks and which side of a change is which.
+    fn replay(
+        &self,
+        from: &str,
+        sides: impl Fn(&Change) -> (&Option<Todo>, &Option<Todo>),
+        kind: impl Fn(u64) -> EventKind,
+    ) -> TxResult<Option<Event>> {
+        let Some((rest, seq)) = self.meta.get(from)?.and_then(|stack| history::pop(&stack)) else {
+            return Ok(None);
+        };
+        self.meta.insert(from, rest)?;
+        let Some(bytes) = self.events.get(seq.to_be_bytes())? else {
+            // Trimmed from the log, and everything under it on the stack is older still
+            self.meta.remove(from)?;
+            return Ok(None);
+        };
+        let event = history::decode(seq, &crypto::open(self.cipher, "events", &seq.to_be_bytes(), &bytes)?)?;
+
+        for change in event.changes.iter().rev() {
+            let (expected, restored) = sides(change);
+            if self.find(change.id)? != *expected {
+                return Err(TodoError::InvalidInput(format!(
+                    "#{} changed since event {} outside the log, so it cannot be replayed",
+                    change.id, seq
+                ))
+                .into());
+            }
+            self.write(change.id, restored.as_ref())?;
+        }
+        self.kind.set(kind(seq));
+        Ok(Some(event))
+    }
+
+    // Like `get`, but a removed todo is `None` rather than an error
+    fn find(&self, id: u64) -> TxResult<Option<Todo>> {
+        match self.get(id) {
+            Ok(todo) => Ok(Some(todo)),
+            Err(TxError(ConflictableTransactionError::Abort(TodoError::NotFound(_)))) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    // New links must point at existing todos and neither relation may loop back to
+    // `id`. Links kept from `previous` may point at removed todos.
+    fn check_links(&self, id: u64, todo: &Todo, previous: Option<&Todo>) -> TxResult<()> {
+        if todo.parent == Some(id) || todo.blocked_by.contains(&id) {
+            return Err(cycle(format!("#{} cannot be its own subtask or wait on itself", id)));
+        }
+        if let Some(parent) = todo.parent {
+            if previous.and_then(|p| p.parent) != Some(parent) {
+                self.get(parent)?;
+            }
+            let mut seen = HashSet::new();
+            let mut at = Some(parent);
+            while let Some(current) = at {
+                if current == id {
+                    return Err(cycle(format!("#{} cannot be a subtask of #{}, which is under it", id, parent)));
+                }
+                if !seen.insert(current) {
+                    break; // an older loop that does not involve `id`
+                }
+                at = self.find(current)?.and_then(|t| t.parent);
+            }
+        }
+
+        for &blocker in &todo.blocked_by {
+            if !previous.is_some_and(|p| p.blocked_by.contains(&blocker)) {
+                self.get(blocker)?;
+            }
+        }
+        let mut seen = HashSet::new();
+        let mut stack: Vec<(u64, u64)> = todo.blocked_by.iter().map(|&b| (b, b)).collect();
+        while let Some((blocker, current)) = stack.pop() {
+            if current == id {
+                return Err(cycle(format!("#{} cannot wait on #{}, which waits on it", id, blocker)));
+            }
+            if seen.insert(current) {
+                if let Some(other) = self.find(current)? {
+                    stack.extend(other.blocked_by.iter().map(|&next| (blocker, next)));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Stores or removes one todo and stamps what changed on its sync record
+    fn write(&self, id: u64, todo: Option<&Todo>) -> TxResult<Option<Todo>> {
+        let previous = self.put(id, todo)?;
+        if previous.as_ref() != todo {
+            self.track(id, previous.as_ref(), todo)?;
+        }
+        if todo.is_none_or(|todo| todo.done) {
+            self.stop_timer(Some(id), Local::now().timestamp())?; // no time is logged on a finished todo
+        }
+        Ok(previous)
+    }
+
+    // Closes the running interval at `at`, if the timer runs at all and, given `on`, on that todo
+    fn stop_timer(&self, on: Option<u64>, at: i64) -> TxResult<Option<Interval>> {
+        let Some(key) = self.meta.get(TIMER)? else {
+            return Ok(None);
+        };
+        let mut interval = timelog::decode(&key, &[])?;
+        if on.is_some_and(|id| id != interval.id) {
+            return Ok(None);
+        }
+        let end = at.max(interval.start); // the clock may have been turned back meanwhile
+        self.time.insert(key, &end.to_be_bytes())?;
+        self.meta.remove(TIMER)?;
+        interval.end = Some(end);
+        Ok(Some(interval))
+    }
+
+    // Stores or removes one record and swaps its index entries, returning the previous record
+    fn put(&self, id: u64, todo: Option<&Todo>) -> TxResult<Option<Todo>> {
+        let key = id.to_be_bytes();
+        let previous = match todo {
+            Some(todo) => self.todos.insert(&key, crypto::seal(self.cipher, "todos", &key, &Todo::serialize(todo)))?,
+            None => self.todos.remove(&key)?,
+        };
+        let previous = match previous {
+            Some(bytes) => Some(read_todo(self.cipher, id, &bytes)?),
+            None => None,
+        };
+
+        if let Some(old) = &previous {
+            for (index, key) in index::entries(id, old, self.cipher.is_none()) {
+                self.index(index).remove(key)?;
+            }
+        }
+        if let Some(new) = todo {
+            for (index, key) in index::entries(id, new, self.cipher.is_none()) {
+                self.index(index).insert(key, &[])?;
+            }
+        }
+
+        if previous.as_ref() != todo {
+            self.changes.borrow_mut().push(Change {
+                id,
+                before: previous.clone(),
+                after: todo.cloned(),
+            });
+        }
+        Ok(previous)
+    }
+
+    // Applies a peer's records. Local IDs are handed out first, so links between
+    // incoming todos resolve whatever order they arrive in.
+    fn merge(&self, records: &[Record], seen: &Seen) -> TxResult<usize> {
+        let mut ids = Vec::new();
+        for record in records {
+            let id = match self.local_id(record.uid)? {
+                Some(id) => id,
+                None => {
+                    let id = self.next("next_id")?;
+                    self.uids.insert(&record.uid.key(), &id.to_be_bytes())?;
+                    id
+                }
+            };
+            ids.push(id);
+        }
+
+        let mut changed = Vec::new();
+        for (record, &id) in records.iter().zip(&ids) {
+            let merged = match self.record(id)? {
+                Some(mut local) => {
+                    if !local.merge(record) {
+                        continue;
+                    }
+                    local
+                }
+                None => record.clone(),
+            };
+            self.save_record(id, &merged)?;
+            let todo = match merged.deleted {
+                true => None,
+                false => Some(self.localize(&merged)?),
+            };
+            if self.put(id, todo.as_ref())?.as_ref() != todo.as_ref() {
+                changed.push(id);
+            }
+        }
+
+        let clock = records.iter().map(Record::clock).max().unwrap_or(0);
+        if clock > self.counter("clock")? {
+            self.meta.insert("clock", &clock.to_be_bytes())?;
+        }
+        let mut ours = match self.meta.get("seen")? {
+            Some(bytes) => sync::decode_seen(&bytes)?,
+            None => Seen::new(),
+        };
+        for (&device, &clock) in seen {
+            let entry = ours.entry(device).or_default();
+            *entry = clock.max(*entry);
+        }
+        self.meta.insert("seen", sync::encode_seen(&ours))?;
+
+        // Links set on two devices can loop together where neither did alone; the
+        // loop is broken by unlinking the todo, which then spreads like any edit
+        for &id in &changed {
+            if let Some(mut todo) = self.find(id)? {
+                match self.check_links(id, &todo, Some(&todo)) {
+                    Err(TxError(ConflictableTransactionError::Abort(TodoError::InvalidInput(_)))) => {
+                        todo.parent = None;
+                        todo.blocked_by.clear();
+                        self.write(id, Some(&todo))?;
+                    }
+                    other => other?,
+                }
+            }
+        }
+        self.kind.set(EventKind::Sync);
+        Ok(changed.len())
+    }
+
+    // Stamps the fields a local write changed, starting a record for a todo without one
+    fn track(&self, id: u64, previous: Option<&Todo>, todo: Option<&Todo>) -> TxResult<()> {
+        let stamp = Stamp {
+            clock: self.next("clock")?,
+            device: self.device()?,
+        };
+        let record = match self.record(id)? {
+            Some(mut record) => {
+                let current = match todo {
+                    Some(todo) => self.globalize(record.uid, todo, stamp)?,
+                    None => Record {
+                        deleted: true,
+                        ..record.clone()
+                    },
+                };
+                record.overwrite(&current, stamp);
+                record
+            }
+            None => match todo.or(previous) {
+                Some(latest) => Record {
+                    deleted: todo.is_none(),
+                    ..self.globalize(Uid { device: stamp.device, id }, latest, stamp)?
+                },
+                None => return Ok(()),
+            },
+        };
+        self.save_record(id, &record)
+    }
+
+    fn record(&self, id: u64) -> TxResult<Option<Record>> {
+        let key = id.to_be_bytes();
+        match self.sync.get(key)? {
+            Some(bytes) => {
+                let record = crypto::open(self.cipher, "sync", &key, &bytes).and_then(|plain| sync::decode(&plain));
+                Ok(Some(record.map_err(|e| e.in_record(id))?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn save_record(&self, id: u64, record: &Record) -> TxResult<()> {
+        let key = id.to_be_bytes();
+        self.sync.insert(&key, crypto::seal(self.cipher, "sync", &key, &sync::encode(record)))?;
+        Ok(())
+    }
+
+    // `todo` as a record with every field stamped `stamp` and links by `Uid`
+    fn globalize(&self, uid: Uid, todo: &Todo, stamp: Stamp) -> TxResult<Record> {
+        let parent = todo.parent.map(|id| self.uid(id)).transpose()?;
+        let blocked_by = todo.blocked_by.iter().map(|&id| self.uid(id)).collect::<TxResult<_>>()?;
+        Ok(Record::new(uid, todo, parent, blocked_by, stamp))
+    }
+
+    // The todo in `record` with links to todos unknown here left out
+    fn localize(&self, record: &Record) -> TxResult<Todo> {
+        let parent = match record.parent {
+            Some(uid) => self.local_id(uid)?,
+            None => None,
+        };
+        let mut blocked_by = Vec::new();
+        for &uid in &record.blocked_by {
+            blocked_by.extend(self.local_id(uid)?);
+        }
+        Ok(record.todo(parent, blocked_by))
+    }
+
+    // A todo without a record was added here before it was first synced
+    fn uid(&self, id: u64) -> TxResult<Uid> {
+        match self.record(id)? {
+            Some(record) => Ok(record.uid),
+            None => Ok(Uid {
+                device: self.device()?,
+                id,
+            }),
+        }
+    }
+
+    fn local_id(&self, uid: Uid) -> TxResult<Option<u64>> {
+        if uid.device == self.device()? {
+            return Ok(Some(uid.id));
+        }
+        match self.uids.get(uid.key())? {
+            Some(bytes) => Ok(Some(key_to_id(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    // This database's device ID, picked on first use
+    fn device(&self) -> TxResult<u64> {
+        if let Some(bytes) = self.meta.get("device")? {
+            return Ok(key_to_id(&bytes)?);
+        }
+        let device = sync::new_device();
+        self.meta.insert("device", &device.to_be_bytes())?;
+        Ok(device)
+    }
+
+    fn index(&self, index: Index) -> &TransactionalTree {
+        match index {
+            Index::Tag => self.tags,
+            Index::Project => self.projects,
+            Index::Due => self.due,
+            Index::Parent => self.children,
+            Index::Word => self.words,
+        }
+    }
+}
+
+// Decodes a stored todo, opening it first when the database is encrypted
+fn read_todo(cipher: Option<&Cipher>, id: u64, bytes: &[u8]) -> Result<Todo> {
+    crypto::open(cipher, "todos", &id.to_be_bytes(), bytes)
+        .and_then(|plain| Todo::deserialize(&plain))
+        .map_err(|e| e.in_record(id))
+}
+
+// The `meta` key holding the occurrence that completing `id` added. It outlives
+// that occurrence, so undoing a reopen finds it again; IDs are never reused
+fn spawned_key(id: u64) -> Vec<u8> {
+    [&b"spawned:"[..], &id.to_be_bytes()].concat()
+}
+
+fn cycle(message: String) -> TxError {
+    TodoError::InvalidInput(message).into()
+}
+
+fn key_to_id(key: &[u8]) -> Result<u64> {
+    key.try_into()
+        .map(u64::from_be_bytes)
+        .map_err(|_| TodoError::decode("corrupt key"))
+}
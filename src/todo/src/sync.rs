@@ -0,0 +1,419 @@
+//! Keeping one list on several devices.
+//!
+//! Next to every todo the store keeps a [`Record`] in the `sync` tree: its fields,
+//! each with the [`Stamp`] of the write that last set it, and whether it was
+//! removed. Devices swap records and keep, field by field, the value with the later
+//! stamp. Edits to different fields of one todo on two devices both survive, edits
+//! to the same field go to the later one, and every device ends up with the same
+//! values whichever order records arrive in. A removed todo stays behind as a
+//! record marked deleted, so the removal spreads like any other edit.
+//!
+//! Stamps are Lamport clocks, with the device as tie-break. Each device also keeps
+//! a version vector, [`Seen`], holding the highest clock it has from every device,
+//! so a peer only has to send the records with a stamp above it.
+//!
+//! IDs stay local: a todo from another device gets the next free ID here, and
+//! [`Uid`] names it across devices. `todo serve` answers on `/sync`, where `GET`
+//! returns the server's [`Seen`] and `POST` takes a [`Batch`], merges it and
+//! replies with the batch the caller is missing.
+//!
+//! Every batch carries the [`PROTOCOL`] it is written in, and every answer names
+//! the server's in an `X-Todo-Sync` header; either side refuses a peer speaking
+//! another one before reading its records. Batches from before the field are
+//! protocol 1.
+
+use bincode::config::standard;
+use bincode::{Decode, Encode};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::RandomState;
+use std::collections::BTreeMap;
+use std::hash::{BuildHasher, Hasher};
+use std::io::Read;
+use std::time::{Duration, SystemTime};
+
+use crate::error::{Result, TodoError};
+use crate::{Prior, Recurrence, Todo, TodoStore};
+
+const VERSION: u8 = 2;
+const STAMPS: usize = 12; // one per field group in `each_field!`
+
+/// Version of the `/sync` exchange, raised whenever the JSON of a batch changes.
+pub const PROTOCOL: u64 = 2;
+const PROTOCOL_HEADER: &str = "X-Todo-Sync";
+
+/// When a field was last written: the writer's clock, then its device to break ties.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Encode, Decode, Serialize, Deserialize)]
+pub struct Stamp {
+    pub clock: u64,
+    pub device: u64,
+}
+
+/// A todo's identity on every device: where it was added and its ID there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, Serialize, Deserialize)]
+pub struct Uid {
+    pub device: u64,
+    pub id: u64,
+}
+
+impl Uid {
+    pub(crate) fn key(self) -> [u8; 16] {
+        let mut key = [0; 16];
+        key[..8].copy_from_slice(&self.device.to_be_bytes());
+        key[8..].copy_from_slice(&self.id.to_be_bytes());
+        key
+    }
+}
+
+/// The highest clock held from each device.
+pub type Seen = BTreeMap<u64, u64>;
+
+/// A todo as it is replicated, with links by [`Uid`]. `stamps` has one entry per
+/// field in declaration order; `done` and `completed` share one.
+#[derive(Debug, Clone, PartialEq, Encode, Decode, Serialize, Deserialize)]
+pub struct Record {
+    pub uid: Uid,
+    pub stamps: [Stamp; STAMPS],
+    pub thing: String,
+    pub priority: Prior,
+    pub done: bool,
+    pub completed: Option<i64>,
+    pub due: Option<i64>,
+    pub created: i64,
+    pub tags: Vec<String>,
+    pub project: Option<String>,
+    pub parent: Option<Uid>,
+    pub blocked_by: Vec<Uid>,
+    pub recur: Option<Recurrence>,
+    pub deleted: bool,
+    pub notes: Option<String>,
+}
+
+/// Version 1 records, from before notes.
+#[derive(Decode)]
+struct RecordV1 {
+    uid: Uid,
+    stamps: [Stamp; 11],
+    thing: String,
+    priority: Prior,
+    done: bool,
+    completed: Option<i64>,
+    due: Option<i64>,
+    created: i64,
+    tags: Vec<String>,
+    project: Option<String>,
+    parent: Option<Uid>,
+    blocked_by: Vec<Uid>,
+    recur: Option<Recurrence>,
+    deleted: bool,
+}
+
+impl From<RecordV1> for Record {
+    fn from(old: RecordV1) -> Self {
+        let mut stamps = [Stamp::default(); STAMPS];
+        stamps[..11].copy_from_slice(&old.stamps);
+        Record {
+            uid: old.uid,
+            stamps,
+            thing: old.thing,
+            priority: old.priority,
+            done: old.done,
+            completed: old.completed,
+            due: old.due,
+            created: old.created,
+            tags: old.tags,
+            project: old.project,
+            parent: old.parent,
+            blocked_by: old.blocked_by,
+            recur: old.recur,
+            deleted: old.deleted,
+            notes: None,
+        }
+    }
+}
+
+// Expands `$apply!(<stamp index>, <field>...)` for every field group of `Record`
+macro_rules! each_field {
+    ($apply:ident) => {
+        $apply!(0, thing);
+        $apply!(1, priority);
+        $apply!(2, done, completed);
+        $apply!(3, due);
+        $apply!(4, created);
+        $apply!(5, tags);
+        $apply!(6, project);
+        $apply!(7, parent);
+        $apply!(8, blocked_by);
+        $apply!(9, recur);
+        $apply!(10, deleted);
+        $apply!(11, notes);
+    };
+}
+
+impl Record {
+    /// `todo` with every field stamped `stamp`.
+    pub(crate) fn new(uid: Uid, todo: &Todo, parent: Option<Uid>, blocked_by: Vec<Uid>, stamp: Stamp) -> Self {
+        Record {
+            uid,
+            stamps: [stamp; STAMPS],
+            thing: todo.thing.clone(),
+            priority: todo.priority,
+            done: todo.done,
+            completed: todo.completed,
+            due: todo.due,
+            created: todo.created,
+            tags: todo.tags.clone(),
+            project: todo.project.clone(),
+            parent,
+            blocked_by,
+            recur: todo.recur,
+            deleted: false,
+            notes: todo.notes.clone(),
+        }
+    }
+
+    /// The todo with links already mapped to local IDs.
+    pub(crate) fn todo(&self, parent: Option<u64>, blocked_by: Vec<u64>) -> Todo {
+        Todo {
+            thing: self.thing.clone(),
+            priority: self.priority,
+            done: self.done,
+            due: self.due,
+            created: self.created,
+            completed: self.completed,
+            tags: self.tags.clone(),
+            project: self.project.clone(),
+            parent,
+            blocked_by,
+            recur: self.recur,
+            notes: self.notes.clone(),
+        }
+    }
+
+    /// Records a local write: the fields where `current` differs take its value and `stamp`.
+    pub(crate) fn overwrite(&mut self, current: &Record, stamp: Stamp) {
+        macro_rules! take {
+            ($at:expr, $($field:ident),+) => {
+                if $(self.$field != current.$field)||+ {
+                    $(self.$field = current.$field.clone();)+
+                    self.stamps[$at] = stamp;
+                }
+            };
+        }
+        each_field!(take);
+    }
+
+    /// Takes every field `other` wrote later and returns whether anything changed.
+    pub fn merge(&mut self, other: &Record) -> bool {
+        let mut changed = false;
+        macro_rules! take {
+            ($at:expr, $($field:ident),+) => {
+                if other.stamps[$at] > self.stamps[$at] {
+                    $(self.$field = other.$field.clone();)+
+                    self.stamps[$at] = other.stamps[$at];
+                    changed = true;
+                }
+            };
+        }
+        each_field!(take);
+        changed
+    }
+
+    /// Whether some field was written after what `seen` covers.
+    pub fn newer_than(&self, seen: &Seen) -> bool {
+        self.stamps
+            .iter()
+            .any(|stamp| stamp.clock > seen.get(&stamp.device).copied().unwrap_or(0))
+    }
+
+    /// The latest clock among the stamps.
+    pub fn clock(&self) -> u64 {
+        self.stamps.iter().map(|stamp| stamp.clock).max().unwrap_or(0)
+    }
+}
+
+/// One side of an exchange: what the sender has seen and the records it sends.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Batch {
+    #[serde(default = "first_protocol")]
+    pub protocol: u64,
+    pub seen: Seen,
+    pub records: Vec<Record>,
+}
+
+fn first_protocol() -> u64 {
+    1
+}
+
+/// What [`sync`] did: records sent, and todos changed here by the reply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Synced {
+    pub sent: usize,
+    pub received: usize,
+}
+
+/// Brings `store` and the server at `url` (e.g. `http://10.0.0.2:7878`) level:
+/// fetches what the server has seen, sends it the records it lacks and merges
+/// the records it replies with.
+pub fn sync(store: &TodoStore, url: &str) -> Result<Synced> {
+    let url = format!("{}/sync", url.trim_end_matches('/'));
+    let agent = ureq::AgentBuilder::new().timeout(Duration::from_secs(30)).build();
+
+    let response = agent.get(&url).call().map_err(|e| failed(&url, e))?;
+    // Servers from before the header speak protocol 1
+    let protocol = response.header(PROTOCOL_HEADER).map_or(Some(1), |value| value.parse().ok());
+    if protocol != Some(PROTOCOL) {
+        return Err(TodoError::Sync(format!("{} {}", url, mismatch(protocol))));
+    }
+    let theirs: Seen = read_json(response.into_reader())?;
+    let batch = Batch {
+        protocol: PROTOCOL,
+        records: store.changes_since(&theirs)?,
+        seen: store.seen()?,
+    };
+    let body = serde_json::to_string(&batch).map_err(|e| TodoError::Sync(e.to_string()))?;
+    let response = agent
+        .post(&url)
+        .set("Content-Type", "application/json")
+        .send_string(&body)
+        .map_err(|e| failed(&url, e))?;
+    let reply = read_json(response.into_reader())?;
+    let reply = read_batch(reply).map_err(|e| TodoError::Sync(format!("{} {}", url, e)))?;
+    let received = store.merge(&reply.records, &reply.seen)?;
+    Ok(Synced {
+        sent: batch.records.len(),
+        received,
+    })
+}
+
+fn failed(url: &str, e: ureq::Error) -> TodoError {
+    match e {
+        // The server puts its error message in the body
+        ureq::Error::Status(code, response) => {
+            let message = response.into_string().unwrap_or_default();
+            TodoError::Sync(format!("{} answered {}: {}", url, code, message.trim()))
+        }
+        other => TodoError::Sync(other.to_string()),
+    }
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(reader: impl Read) -> Result<T> {
+    serde_json::from_reader(reader).map_err(|e| TodoError::Sync(format!("unreadable reply: {}", e)))
+}
+
+// Reads a batch only once it is known to be in this protocol, so a peer on
+// another version is told so instead of getting a parse error about its records
+fn read_batch(value: serde_json::Value) -> std::result::Result<Batch, String> {
+    let protocol = value.get("protocol").map_or(Some(1), serde_json::Value::as_u64);
+    if protocol != Some(PROTOCOL) {
+        return Err(mismatch(protocol));
+    }
+    serde_json::from_value(value).map_err(|e| format!("sent an unreadable batch: {}", e))
+}
+
+fn mismatch(protocol: Option<u64>) -> String {
+    let theirs = protocol.map_or_else(|| "an unknown".to_string(), |p| p.to_string());
+    format!(
+        "speaks sync protocol {} but this side speaks {}; run the same version of todo on both",
+        theirs, PROTOCOL
+    )
+}
+
+/// The other end of [`sync`]: an HTTP server over one store.
+pub struct Server(tiny_http::Server);
+
+impl Server {
+    /// Listens on `addr`, e.g. `0.0.0.0:7878`; port 0 picks a free one.
+    pub fn bind(addr: &str) -> Result<Self> {
+        tiny_http::Server::http(addr)
+            .map(Server)
+            .map_err(|e| TodoError::Sync(format!("cannot listen on {}: {}", addr, e)))
+    }
+
+    /// The address it listens on, with the actual port.
+    pub fn addr(&self) -> String {
+        self.0.server_addr().to_string()
+    }
+
+    /// Answers requests one at a time until the process ends.
+    pub fn run(&self, store: &TodoStore) -> Result<()> {
+        for mut request in self.0.incoming_requests() {
+            let (status, body) = match (request.method(), request.url()) {
+                (tiny_http::Method::Get, "/sync") => json(store.seen()),
+                (tiny_http::Method::Post, "/sync") => {
+                    let batch = serde_json::from_reader(request.as_reader())
+                        .map_err(|e| format!("sent an unreadable batch: {}", e))
+                        .and_then(read_batch);
+                    match batch {
+                        Ok(batch) => json(exchange(store, batch)),
+                        Err(e) => (400, format!("the client {}", e)),
+                    }
+                }
+                _ => (404, "only /sync is served".to_string()),
+            };
+            let header = tiny_http::Header::from_bytes("Content-Type", "application/json").unwrap();
+            let protocol = tiny_http::Header::from_bytes(PROTOCOL_HEADER, PROTOCOL.to_string()).unwrap();
+            let response = tiny_http::Response::from_string(body)
+                .with_status_code(status)
+                .with_header(header)
+                .with_header(protocol);
+            let _ = request.respond(response); // a caller that hung up does not stop the server
+        }
+        Ok(())
+    }
+}
+
+fn exchange(store: &TodoStore, batch: Batch) -> Result<Batch> {
+    store.merge(&batch.records, &batch.seen)?;
+    Ok(Batch {
+        protocol: PROTOCOL,
+        records: store.changes_since(&batch.seen)?,
+        seen: store.seen()?,
+    })
+}
+
+// 200 with `value` as JSON, or 500 with the error as plain text
+fn json(value: Result<impl Serialize>) -> (u16, String) {
+    match value.map(|value| serde_json::to_string(&value)) {
+        Ok(Ok(body)) => (200, body),
+        Ok(Err(e)) => (500, e.to_string()),
+        Err(e) => (500, e.to_string()),
+    }
+}
+
+/// A random device ID, picked once per database.
+pub(crate) fn new_device() -> u64 {
+    let mut hasher = RandomState::new().build_hasher();
+    let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default();
+    hasher.write_u128(now.as_nanos());
+    hasher.write_u32(std::process::id());
+    hasher.finish().max(1)
+}
+
+pub(crate) fn encode(record: &Record) -> Vec<u8> {
+    let mut bytes = vec![VERSION];
+    bincode::encode_into_std_write(record, &mut bytes, standard()).expect("encoding a record into memory");
+    bytes
+}
+
+pub(crate) fn decode(bytes: &[u8]) -> Result<Record> {
+    let corrupt = || TodoError::decode("unreadable sync record");
+    match bytes {
+        [VERSION, payload @ ..] => bincode::decode_from_slice(payload, standard())
+            .map(|(record, _)| record)
+            .map_err(|_| corrupt()),
+        [1, payload @ ..] => bincode::decode_from_slice::<RecordV1, _>(payload, standard())
+            .map(|(record, _)| record.into())
+            .map_err(|_| corrupt()),
+        _ => Err(corrupt()),
+    }
+}
+
+pub(crate) fn encode_seen(seen: &Seen) -> Vec<u8> {
+    bincode::encode_to_vec(seen, standard()).expect("encoding a version vector into memory")
+}
+
+pub(crate) fn decode_seen(bytes: &[u8]) -> Result<Seen> {
+    bincode::decode_from_slice(bytes, standard())
+        .map(|(seen, _)| seen)
+        .map_err(|_| TodoError::decode("unreadable version vector"))
+}
@@ -0,0 +1,93 @@
+//! Time spent on todos.
+//!
+//! [`TodoStore::start`] opens an interval against one todo and [`TodoStore::stop`]
+//! closes it. The `time` tree holds one key per interval, `<start> <id>` with the
+//! start made sortable like due dates, and the big-endian end as its value, empty
+//! while it runs. `meta` keeps the key of the running interval under `timer`, so a
+//! second timer is refused by the same transaction that would start it, and any
+//! change that completes or removes the timed todo (undo and sync included) stops it.
+//!
+//! The log stays on this device: it is neither synced nor part of undo, and an
+//! encrypted store leaves it readable like the indexes, as it holds only IDs and times.
+
+use std::collections::BTreeMap;
+
+use crate::error::{Result, TodoError};
+use crate::index::sortable;
+use crate::TodoStore;
+
+/// The `meta` key of the running interval.
+pub(crate) const TIMER: &str = "timer";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Interval {
+    pub id: u64,
+    pub start: i64, // Unix seconds
+    pub end: Option<i64>, // None while the timer runs
+}
+
+impl Interval {
+    /// Seconds of the interval inside `from..to`, a running one counting up to `now`.
+    pub fn within(&self, from: i64, to: i64, now: i64) -> i64 {
+        let end = self.end.unwrap_or(now).min(to);
+        (end - self.start.max(from)).max(0)
+    }
+}
+
+pub(crate) fn key(id: u64, start: i64) -> Vec<u8> {
+    [sortable(start), id.to_be_bytes()].concat()
+}
+
+pub(crate) fn decode(key: &[u8], value: &[u8]) -> Result<Interval> {
+    let corrupt = || TodoError::decode("corrupt time log entry");
+    if key.len() != 16 {
+        return Err(corrupt());
+    }
+    let (start, id) = key.split_at(8);
+    let start = (u64::from_be_bytes(start.try_into().unwrap()) ^ (1 << 63)) as i64;
+    let end = match value {
+        [] => None,
+        end => Some(i64::from_be_bytes(end.try_into().map_err(|_| corrupt())?)),
+    };
+    Ok(Interval {
+        id: u64::from_be_bytes(id.try_into().unwrap()),
+        start,
+        end,
+    })
+}
+
+/// Seconds per todo, tag and project. A todo counts towards every tag it has now;
+/// removed todos count towards no tag or project, but still towards `total`.
+#[derive(Debug, Default, PartialEq)]
+pub struct Report {
+    pub tasks: BTreeMap<u64, i64>,
+    pub tags: BTreeMap<String, i64>,
+    pub projects: BTreeMap<String, i64>,
+    pub total: i64,
+}
+
+/// Adds up the time logged between `from` and `to`, cutting intervals at both ends.
+pub fn report(store: &TodoStore, from: i64, to: i64, now: i64) -> Result<Report> {
+    let mut report = Report::default();
+    for interval in store.intervals(from, to)? {
+        let seconds = interval.within(from, to, now);
+        if seconds > 0 {
+            *report.tasks.entry(interval.id).or_default() += seconds;
+            report.total += seconds;
+        }
+    }
+    for (&id, &seconds) in &report.tasks {
+        let todo = match store.get(id) {
+            Ok(todo) => todo,
+            Err(TodoError::NotFound(_)) => continue,
+            Err(e) => return Err(e),
+        };
+        for tag in todo.tags {
+            *report.tags.entry(tag).or_default() += seconds;
+        }
+        if let Some(project) = todo.project {
+            *report.projects.entry(project).or_default() += seconds;
+        }
+    }
+    Ok(report)
+}
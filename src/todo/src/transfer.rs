@@ -0,0 +1,461 @@
+//! Export and import in JSON, CSV and todo.txt.
+//!
+//! JSON and CSV carry every field, so exporting and importing again gives back the
+//! same todos. todo.txt keeps the thing, priority, done state, project, tags and due
+//! date exactly, has no place for notes, and its creation and completion dates have
+//! no time of day. Words in the thing that look like todo.txt markup (`+x`, `@x`,
+//! `due:`, `pri:`, `rec:`) are read back as markup.
+//!
+//! Imported todos get fresh IDs. Exported IDs only tie subtasks to their parent and
+//! todos to their blockers, and those links are remapped to the new IDs on import;
+//! links to todos missing from the file are dropped. todo.txt writes them as
+//! `id:`, `parent:` and `dep:` tags.
+
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Timelike};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::dates;
+use crate::error::{Result, TodoError};
+use crate::{Prior, Todo, TodoStore};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Csv,
+    TodoTxt,
+}
+
+impl Format {
+    /// Guesses the format from a file extension.
+    pub fn from_path(path: &Path) -> Option<Format> {
+        match path.extension()?.to_str()?.to_lowercase().as_str() {
+            "json" => Some(Format::Json),
+            "csv" => Some(Format::Csv),
+            "txt" => Some(Format::TodoTxt),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = TodoError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "json" => Ok(Format::Json),
+            "csv" => Ok(Format::Csv),
+            "todo.txt" | "todotxt" | "txt" => Ok(Format::TodoTxt),
+            _ => Err(TodoError::InvalidInput(format!(
+                "unknown format '{}', use json, csv or todo.txt",
+                s
+            ))),
+        }
+    }
+}
+
+// One JSON array element: the todo with its ID alongside
+#[derive(Serialize, Deserialize)]
+struct JsonEntry {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<u64>,
+    #[serde(flatten)]
+    todo: Todo,
+}
+
+// One CSV row. Times are RFC 3339 in local time, tags and blockers are space separated
+#[derive(Serialize, Deserialize)]
+struct CsvRow {
+    id: Option<u64>,
+    thing: String,
+    priority: Prior,
+    done: bool,
+    due: Option<String>,
+    created: Option<String>,
+    completed: Option<String>,
+    tags: String,
+    project: Option<String>,
+    #[serde(default)]
+    parent: Option<u64>,
+    #[serde(default)]
+    blocked_by: String,
+    #[serde(default)]
+    repeat: Option<String>,
+    #[serde(default)]
+    notes: Option<String>,
+}
+
+pub fn export(items: &[(u64, Todo)], format: Format, mut out: impl Write) -> Result<()> {
+    match format {
+        Format::Json => {
+            let entries: Vec<JsonEntry> = items
+                .iter()
+                .map(|(id, todo)| JsonEntry {
+                    id: Some(*id),
+                    todo: todo.clone(),
+                })
+                .collect();
+            serde_json::to_writer_pretty(&mut out, &entries).map_err(io::Error::from)?;
+            writeln!(out)?;
+        }
+        Format::Csv => {
+            let mut writer = csv::Writer::from_writer(&mut out);
+            for (id, todo) in items {
+                writer.serialize(to_row(*id, todo)).map_err(io::Error::other)?;
+            }
+            writer.flush()?;
+        }
+        Format::TodoTxt => {
+            // `id:` only on todos that take part in a link, to keep the rest plain
+            let linked: HashSet<u64> = items
+                .iter()
+                .flat_map(|(_, todo)| todo.parent.iter().chain(&todo.blocked_by).copied())
+                .collect();
+            for (id, todo) in items {
+                let show_id = linked.contains(id) || todo.parent.is_some() || !todo.blocked_by.is_empty();
+                writeln!(out, "{}", to_todo_txt(show_id.then_some(*id), todo))?;
+            }
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Reads todos in `format`, in file order, each with the ID it was exported under.
+pub fn parse(format: Format, input: impl Read) -> Result<Vec<(Option<u64>, Todo)>> {
+    let entries: Vec<(Option<u64>, Todo)> = match format {
+        Format::Json => {
+            let entries: Vec<JsonEntry> = serde_json::from_reader(input)
+                .map_err(|e| TodoError::InvalidInput(format!("invalid JSON: {}", e)))?;
+            entries.into_iter().map(|entry| (entry.id, entry.todo)).collect()
+        }
+        Format::Csv => csv::Reader::from_reader(input)
+            .deserialize()
+            .map(|row| {
+                let row = row.map_err(|e| TodoError::InvalidInput(format!("invalid CSV: {}", e)))?;
+                from_row(row)
+            })
+            .collect::<Result<_>>()?,
+        Format::TodoTxt => {
+            let mut entries = Vec::new();
+            for (number, line) in BufReader::new(input).lines().enumerate() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let entry = from_todo_txt(&line)
+                    .map_err(|e| TodoError::InvalidInput(format!("line {}: {}", number + 1, e)))?;
+                entries.push(entry);
+            }
+            entries
+        }
+    };
+    entries
+        .into_iter()
+        .map(|(id, todo)| Ok((id, normalize(todo)?)))
+        .collect()
+}
+
+/// What an import did: the IDs it added and how many todos it skipped as duplicates.
+#[derive(Debug, Default)]
+pub struct Imported {
+    pub added: Vec<u64>,
+    pub duplicates: usize,
+}
+
+// Where an exported ID ends up: a todo already in the store, or the n-th new one
+#[derive(Clone, Copy)]
+enum Target {
+    Existing(u64),
+    Fresh(usize),
+}
+
+/// Adds `entries` from [`parse`] in one transaction, skipping any with the same
+/// thing, project, tags and due date as a todo already in the store or earlier in
+/// `entries`.
+/// Importing the same file twice adds nothing the second time. Links to a skipped
+/// duplicate point at the todo it duplicates.
+pub fn import(store: &TodoStore, entries: &[(Option<u64>, Todo)]) -> Result<Imported> {
+    let mut seen = HashMap::new();
+    for entry in store.iter() {
+        let (id, todo) = entry?;
+        seen.insert(identity(&todo), Target::Existing(id));
+    }
+    let mut fresh: Vec<&Todo> = Vec::new();
+    let mut targets = HashMap::new();
+    for (old_id, todo) in entries {
+        let target = *seen.entry(identity(todo)).or_insert_with(|| {
+            fresh.push(todo);
+            Target::Fresh(fresh.len() - 1)
+        });
+        if let Some(old_id) = old_id {
+            targets.entry(*old_id).or_insert(target);
+        }
+    }
+
+    let added = store.transaction(|tx| {
+        // Links can point forwards in the file, so add everything before linking
+        let mut added = Vec::new();
+        for todo in &fresh {
+            let unlinked = Todo {
+                parent: None,
+                blocked_by: Vec::new(),
+                ..(*todo).clone()
+            };
+            added.push(tx.insert(&unlinked)?);
+        }
+        let resolve = |old_id: &u64| match targets.get(old_id)? {
+            Target::Existing(id) => Some(*id),
+            Target::Fresh(n) => Some(added[*n]),
+        };
+        for (todo, id) in fresh.iter().zip(&added) {
+            if todo.parent.is_none() && todo.blocked_by.is_empty() {
+                continue;
+            }
+            let linked = Todo {
+                parent: todo.parent.as_ref().and_then(resolve),
+                blocked_by: todo.blocked_by.iter().filter_map(resolve).collect(),
+                ..(*todo).clone()
+            };
+            tx.update(*id, &linked)?;
+        }
+        Ok(added)
+    })?;
+    Ok(Imported {
+        duplicates: entries.len() - fresh.len(),
+        added,
+    })
+}
+
+// What makes two todos the same for duplicate detection. The due date tells the
+// occurrences of a recurring todo apart
+fn identity(todo: &Todo) -> (String, Option<String>, Vec<String>, Option<i64>) {
+    let thing = todo.thing.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+    let mut tags = todo.tags.clone();
+    tags.sort();
+    (thing, todo.project.clone(), tags, todo.due)
+}
+
+// Imported todos follow the same rules as ones added on the command line
+fn normalize(mut todo: Todo) -> Result<Todo> {
+    if todo.thing.trim().is_empty() {
+        return Err(TodoError::InvalidInput("a todo without any text".into()));
+    }
+    todo.project = todo.project.filter(|p| !p.is_empty()).map(|p| p.to_lowercase());
+    let mut tags: Vec<String> = Vec::new();
+    for tag in todo.tags.drain(..) {
+        let tag = tag.trim_start_matches('+').to_lowercase();
+        if !tag.is_empty() && !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    todo.tags = tags;
+    todo.blocked_by.sort_unstable();
+    todo.blocked_by.dedup();
+    let anchor = todo.due.map_or_else(Local::now, dates::from_timestamp);
+    todo.recur = todo.recur.map(|rule| rule.anchored(anchor));
+    if !todo.done {
+        todo.completed = None;
+    }
+    Ok(todo)
+}
+
+fn to_row(id: u64, todo: &Todo) -> CsvRow {
+    let time = |seconds: i64| dates::from_timestamp(seconds).to_rfc3339();
+    CsvRow {
+        id: Some(id),
+        thing: todo.thing.clone(),
+        priority: todo.priority,
+        done: todo.done,
+        due: todo.due.map(time),
+        created: (todo.created != 0).then(|| time(todo.created)),
+        completed: todo.completed.map(time),
+        tags: todo.tags.join(" "),
+        project: todo.project.clone(),
+        parent: todo.parent,
+        blocked_by: todo.blocked_by.iter().map(u64::to_string).collect::<Vec<_>>().join(" "),
+        repeat: todo.recur.map(|rule| rule.to_string()),
+        notes: todo.notes.clone(),
+    }
+}
+
+fn from_row(row: CsvRow) -> Result<(Option<u64>, Todo)> {
+    let time = |text: Option<String>| -> Result<Option<i64>> {
+        match text.as_deref().map(str::trim) {
+            None | Some("") => Ok(None),
+            Some(text) => DateTime::parse_from_rfc3339(text)
+                .map(|at| Some(at.timestamp()))
+                .map_err(|_| TodoError::InvalidInput(format!("'{}' is not an RFC 3339 time", text))),
+        }
+    };
+    let blocked_by = row
+        .blocked_by
+        .split_whitespace()
+        .map(|id| id.parse().map_err(|_| TodoError::InvalidInput(format!("'{}' is not a todo id", id))))
+        .collect::<Result<_>>()?;
+    let todo = Todo {
+        thing: row.thing,
+        priority: row.priority,
+        done: row.done,
+        due: time(row.due)?,
+        created: time(row.created)?.unwrap_or(0),
+        completed: time(row.completed)?,
+        tags: row.tags.split_whitespace().map(String::from).collect(),
+        project: row.project,
+        parent: row.parent,
+        blocked_by,
+        recur: row.repeat.filter(|rule| !rule.is_empty()).map(|rule| rule.parse()).transpose()?,
+        notes: row.notes.filter(|notes| !notes.is_empty()),
+    };
+    Ok((row.id, todo))
+}
+
+fn letter(priority: Prior) -> char {
+    (b'A' + priority as u8) as char // Fuck is (A), Chill is (E)
+}
+
+fn from_letter(letter: &str) -> Option<Prior> {
+    match letter {
+        "A" => Some(Prior::Fuck),
+        "B" => Some(Prior::High),
+        "C" => Some(Prior::Medium),
+        "D" => Some(Prior::Soft),
+        "E" => Some(Prior::Chill),
+        _ => None,
+    }
+}
+
+// `x <completed> <created> thing` for done items, which todo.txt writes without a
+// priority, so it moves to a `pri:` tag to survive the round trip
+fn to_todo_txt(id: Option<u64>, todo: &Todo) -> String {
+    let day = |seconds: i64| dates::from_timestamp(seconds).format("%Y-%m-%d").to_string();
+    let mut words = Vec::new();
+    if todo.done {
+        words.push("x".to_string());
+        // todo.txt only allows a creation date after a completion date
+        if let Some(completed) = todo.completed {
+            words.push(day(completed));
+        }
+    } else {
+        words.push(format!("({})", letter(todo.priority)));
+    }
+    if todo.created != 0 && (!todo.done || todo.completed.is_some()) {
+        words.push(day(todo.created));
+    }
+    words.push(todo.thing.clone());
+    if let Some(project) = &todo.project {
+        words.push(format!("+{}", project));
+    }
+    for tag in &todo.tags {
+        words.push(format!("@{}", tag));
+    }
+    if let Some(due) = todo.due {
+        words.push(format!("due:{}", due_text(dates::from_timestamp(due))));
+    }
+    if let Some(rule) = todo.recur {
+        words.push(format!("rec:{}", rule));
+    }
+    if let Some(id) = id {
+        words.push(format!("id:{}", id));
+    }
+    if let Some(parent) = todo.parent {
+        words.push(format!("parent:{}", parent));
+    }
+    for blocker in &todo.blocked_by {
+        words.push(format!("dep:{}", blocker));
+    }
+    if todo.done {
+        words.push(format!("pri:{}", letter(todo.priority)));
+    }
+    words.join(" ")
+}
+
+// A bare date means the end of that day, as it does for `--due`
+fn due_text(at: DateTime<Local>) -> String {
+    match (at.hour(), at.minute(), at.second()) {
+        (23, 59, 0) => at.format("%Y-%m-%d").to_string(),
+        (_, _, 0) => at.format("%Y-%m-%dT%H:%M").to_string(),
+        _ => at.format("%Y-%m-%dT%H:%M:%S").to_string(),
+    }
+}
+
+fn from_todo_txt(line: &str) -> std::result::Result<(Option<u64>, Todo), String> {
+    let mut words = line.split_whitespace().peekable();
+    let mut todo = Todo::new(String::new(), Prior::Medium);
+    todo.created = 0;
+
+    if words.peek() == Some(&"x") {
+        words.next();
+        todo.done = true;
+        todo.completed = words.peek().and_then(|w| parse_day(w)).map(|at| at.timestamp());
+        if todo.completed.is_some() {
+            words.next();
+        }
+    } else if let Some(priority) = words
+        .peek()
+        .and_then(|w| w.strip_prefix('(')?.strip_suffix(')'))
+        .and_then(from_letter)
+    {
+        words.next();
+        todo.priority = priority;
+    }
+    if let Some(created) = words.peek().and_then(|w| parse_day(w)) {
+        words.next();
+        todo.created = created.timestamp();
+    }
+
+    let mut id = None;
+    let mut thing = Vec::new();
+    for word in words {
+        let number = |prefix: &str| word.strip_prefix(prefix).and_then(|n| n.parse::<u64>().ok());
+        if let Some(name) = word.strip_prefix('+').filter(|n| !n.is_empty()) {
+            // One project per todo, further ones become tags
+            if todo.project.is_none() {
+                todo.project = Some(name.to_string());
+            } else {
+                todo.tags.push(name.to_string());
+            }
+        } else if let Some(tag) = word.strip_prefix('@').filter(|t| !t.is_empty()) {
+            todo.tags.push(tag.to_string());
+        } else if let Some(due) = word.strip_prefix("due:") {
+            let at = parse_due(due).ok_or_else(|| format!("cannot understand the due date '{}'", due))?;
+            todo.due = Some(at.timestamp());
+        } else if let Some(rule) = word.strip_prefix("rec:") {
+            todo.recur = Some(rule.parse().map_err(|e: TodoError| e.to_string())?);
+        } else if let Some(priority) = word.strip_prefix("pri:").and_then(from_letter) {
+            todo.priority = priority;
+        } else if let Some(n) = number("id:") {
+            id = Some(n);
+        } else if let Some(n) = number("parent:") {
+            todo.parent = Some(n);
+        } else if let Some(n) = number("dep:") {
+            todo.blocked_by.push(n);
+        } else {
+            thing.push(word);
+        }
+    }
+    todo.thing = thing.join(" ");
+    Ok((id, todo))
+}
+
+fn parse_day(word: &str) -> Option<DateTime<Local>> {
+    let day = NaiveDate::parse_from_str(word, "%Y-%m-%d").ok()?;
+    local(day.and_time(NaiveTime::MIN))
+}
+
+fn parse_due(text: &str) -> Option<DateTime<Local>> {
+    if let Ok(day) = NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+        return local(day.and_hms_opt(23, 59, 0)?);
+    }
+    ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+        .and_then(local)
+}
+
+fn local(naive: NaiveDateTime) -> Option<DateTime<Local>> {
+    Local.from_local_datetime(&naive).earliest()
+}
@@ -0,0 +1,371 @@
+//! Interactive mode, started by `todo` without arguments on a terminal.
+
+use chrono::Local;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+
+use todo::query::Query;
+use todo::{Prior, Result, Todo, TodoError, TodoStore};
+
+use crate::{describe, escape_options, format_item, parse_options, sort, tree};
+
+const PRIORITIES: [Prior; 5] = [Prior::Fuck, Prior::High, Prior::Medium, Prior::Soft, Prior::Chill];
+
+const HELP: &str = "j/k move  a add  e edit  space done  p/P priority  u/r undo/redo  / filter  q quit";
+
+enum Mode {
+    Browse,
+    Filter,
+    Add,
+    Edit(u64),
+}
+
+struct App<'a> {
+    store: &'a TodoStore,
+    items: Vec<(u64, Todo)>, // in display order
+    rows: Vec<(String, Style)>,
+    state: ListState,
+    mode: Mode,
+    input: String,
+    filter: String,
+    status: String,
+}
+
+pub fn run(store: &TodoStore) -> Result<()> {
+    let mut terminal = ratatui::init();
+    let result = App::new(store).and_then(|mut app| app.run(&mut terminal));
+    ratatui::restore();
+    result
+}
+
+impl<'a> App<'a> {
+    fn new(store: &'a TodoStore) -> Result<Self> {
+        let mut app = App {
+            store,
+            items: Vec::new(),
+            rows: Vec::new(),
+            state: ListState::default(),
+            mode: Mode::Browse,
+            input: String::new(),
+            filter: String::new(),
+            status: String::new(),
+        };
+        app.reload()?;
+        Ok(app)
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+                return Ok(());
+            }
+            let outcome = match self.mode {
+                Mode::Browse => match self.browse(key) {
+                    Ok(false) => return Ok(()),
+                    other => other.map(|_| ()),
+                },
+                _ => self.type_key(key),
+            };
+            // Mistakes end up in the status line rather than ending the session
+            if let Err(e) = outcome {
+                self.status = e.to_string();
+            }
+        }
+    }
+
+    // Returns false to quit
+    fn browse(&mut self, key: KeyEvent) -> Result<bool> {
+        self.status.clear();
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(false),
+            KeyCode::Char('j') | KeyCode::Down => self.state.select_next(),
+            KeyCode::Char('k') | KeyCode::Up => self.state.select_previous(),
+            KeyCode::Char('g') | KeyCode::Home => self.state.select_first(),
+            KeyCode::Char('G') | KeyCode::End => self.state.select_last(),
+            KeyCode::PageDown => self.state.scroll_down_by(10),
+            KeyCode::PageUp => self.state.scroll_up_by(10),
+            KeyCode::Char('/') => {
+                self.input = self.filter.clone();
+                self.mode = Mode::Filter;
+            }
+            KeyCode::Char('a') => {
+                self.input.clear();
+                self.mode = Mode::Add;
+            }
+            KeyCode::Char('e') | KeyCode::Enter => {
+                if let Some((id, todo)) = self.selected() {
+                    let (id, thing) = (*id, escape_options(&todo.thing));
+                    self.input = thing;
+                    self.mode = Mode::Edit(id);
+                }
+            }
+            KeyCode::Char(' ') | KeyCode::Char('x') => self.toggle_done()?,
+            KeyCode::Char('p') => self.cycle_priority(1)?,
+            KeyCode::Char('P') => self.cycle_priority(PRIORITIES.len() - 1)?,
+            KeyCode::Char('u') => self.step_history(true)?,
+            KeyCode::Char('r') => self.step_history(false)?,
+            _ => {}
+        }
+        Ok(true)
+    }
+
+    // Keys while the bottom line is taking text
+    fn type_key(&mut self, key: KeyEvent) -> Result<()> {
+        match key.code {
+            KeyCode::Esc => {
+                if let Mode::Filter = self.mode {
+                    self.filter.clear(); // Esc drops the filter, Enter keeps it
+                    self.reload()?;
+                }
+                self.mode = Mode::Browse;
+                self.status.clear();
+            }
+            KeyCode::Enter => {
+                let input = self.input.clone();
+                match self.mode {
+                    Mode::Add => self.add(&input)?, // on errors the text stays for fixing
+                    Mode::Edit(id) => self.edit(id, &input)?,
+                    Mode::Filter | Mode::Browse => {}
+                }
+                self.input.clear();
+                self.mode = Mode::Browse;
+            }
+            KeyCode::Backspace => {
+                self.input.pop();
+                self.live_filter();
+            }
+            KeyCode::Char(c) => {
+                self.input.push(c);
+                self.live_filter();
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    // Refilters on every key; a half-typed term keeps the last good result on screen
+    fn live_filter(&mut self) {
+        if !matches!(self.mode, Mode::Filter) {
+            self.status.clear(); // an old error no longer applies to what is being typed
+            return;
+        }
+        let previous = std::mem::replace(&mut self.filter, self.input.clone());
+        match self.reload() {
+            Ok(()) => self.status.clear(),
+            Err(e) => {
+                self.filter = previous;
+                self.status = e.to_string();
+            }
+        }
+    }
+
+    fn add(&mut self, input: &str) -> Result<()> {
+        let (words, options) = parse_options(&split_words(input), false)?;
+        if words.is_empty() {
+            return Err(TodoError::InvalidInput("nothing to add".into()));
+        }
+        let mut todo = Todo::new(words.join(" "), Prior::Medium);
+        options.apply(&mut todo);
+        let id = self.store.insert(&todo)?;
+        self.reload()?;
+        self.select(id);
+        self.status = format!("added #{}", id);
+        Ok(())
+    }
+
+    fn edit(&mut self, id: u64, input: &str) -> Result<()> {
+        let (words, options) = parse_options(&split_words(input), true)?;
+        let mut todo = self.store.get(id)?;
+        if !words.is_empty() {
+            todo.thing = words.join(" ");
+        }
+        options.apply(&mut todo);
+        self.store.update(id, &todo)?;
+        self.reload()
+    }
+
+    fn toggle_done(&mut self) -> Result<()> {
+        let Some((id, todo)) = self.selected() else {
+            return Ok(());
+        };
+        let (id, done) = (*id, todo.done);
+        let spawned = self.store.transaction(|tx| {
+            if done {
+                tx.reopen(id)?;
+                Ok(None)
+            } else {
+                tx.complete(id)
+            }
+        })?;
+        let open_children = self.store.children(id)?.iter().filter(|(_, child)| !child.done).count();
+        self.status = match spawned {
+            Some(next) => format!("#{} repeats as #{}", id, next),
+            None if !done && open_children > 0 => format!("#{} still has {} open subtask(s)", id, open_children),
+            None => String::new(),
+        };
+        self.reload()
+    }
+
+    // Steps through the levels in declaration order, wrapping around
+    fn cycle_priority(&mut self, step: usize) -> Result<()> {
+        let Some((id, todo)) = self.selected() else {
+            return Ok(());
+        };
+        let (id, mut todo) = (*id, todo.clone());
+        let at = PRIORITIES.iter().position(|p| *p == todo.priority).unwrap_or_default();
+        todo.priority = PRIORITIES[(at + step) % PRIORITIES.len()];
+        self.store.update(id, &todo)?;
+        self.reload()
+    }
+
+    fn step_history(&mut self, undo: bool) -> Result<()> {
+        let (name, event) = if undo { ("undo", self.store.undo()?) } else { ("redo", self.store.redo()?) };
+        self.status = match event {
+            Some(event) => format!("{} {}", name, describe(&event)),
+            None => format!("nothing to {}", name),
+        };
+        self.reload()
+    }
+
+    fn selected(&self) -> Option<&(u64, Todo)> {
+        self.items.get(self.state.selected()?)
+    }
+
+    fn select(&mut self, id: u64) {
+        if let Some(at) = self.items.iter().position(|(item, _)| *item == id) {
+            self.state.select(Some(at));
+        }
+    }
+
+    // Reads the filtered list again, keeping the same todo selected where it can
+    fn reload(&mut self) -> Result<()> {
+        let now = Local::now();
+        let query = Query::parse(&split_words(&self.filter), now)?;
+        let mut items = self.store.query(&query)?;
+        sort(&mut items);
+
+        let mut rows = Vec::new();
+        let mut ordered = Vec::new();
+        for ((id, todo), depth) in tree(&items) {
+            let style = if todo.done {
+                Style::default().fg(Color::DarkGray)
+            } else if todo.is_overdue(now.timestamp()) {
+                Style::default().fg(Color::Red)
+            } else {
+                Style::default()
+            };
+            rows.push((format_item(self.store, *id, todo, now, depth)?, style));
+            ordered.push((*id, todo.clone()));
+        }
+
+        let current = self.selected().map(|(id, _)| *id);
+        self.items = ordered;
+        self.rows = rows;
+        match current {
+            Some(id) if self.items.iter().any(|(item, _)| *item == id) => self.select(id),
+            _ => self.state.select((!self.items.is_empty()).then_some(0)),
+        }
+        Ok(())
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [list_area, input_area, help_area] =
+            Layout::vertical([Constraint::Min(1), Constraint::Length(1), Constraint::Length(1)]).areas(frame.area());
+
+        let list = List::new(self.rows.iter().map(|(row, style)| ListItem::new(row.as_str()).style(*style)))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, list_area, &mut self.state);
+
+        let prompt = match self.mode {
+            Mode::Browse if !self.status.is_empty() => self.status.clone(),
+            Mode::Browse if !self.filter.is_empty() => format!("filter: {}", self.filter),
+            Mode::Browse => String::new(),
+            Mode::Filter => format!("/{}", self.input),
+            Mode::Add => format!("add: {}", self.input),
+            Mode::Edit(id) => format!("edit #{}: {}", id, self.input),
+        };
+        frame.render_widget(Paragraph::new(prompt.as_str()), input_area);
+        if !matches!(self.mode, Mode::Browse) {
+            let x = input_area.x + prompt.chars().count() as u16;
+            frame.set_cursor_position((x.min(input_area.right().saturating_sub(1)), input_area.y));
+        }
+
+        let help = match self.mode {
+            Mode::Browse => HELP,
+            Mode::Filter => "Enter keep filter  Esc clear",
+            Mode::Add | Mode::Edit(_) => "text +tag project:x --due tomorrow --priority high  Enter save  Esc cancel",
+        };
+        let help = if !matches!(self.mode, Mode::Browse) && !self.status.is_empty() {
+            Line::from(self.status.as_str()).style(Style::default().fg(Color::Red))
+        } else {
+            Line::from(help).style(Style::default().fg(Color::DarkGray))
+        };
+        frame.render_widget(Paragraph::new(help), help_area);
+    }
+}
+
+// Splits typed text into words like a shell would for double quotes, so
+// `--due "tomorrow 9am"` works the same as on the command line
+fn split_words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut quoted = false;
+    let mut started = false;
+    for c in text.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                started = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if started {
+                    words.push(std::mem::take(&mut word));
+                    started = false;
+                }
+            }
+            c => {
+                word.push(c);
+                started = true;
+            }
+        }
+    }
+    if started {
+        words.push(word);
+    }
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn editing_keeps_words_that_look_like_options() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = TodoStore::open(dir.path()).unwrap();
+        let thing = "-draft the plan, +1 for --due dates and \\o/";
+        let id = store.insert(&Todo::new(thing.into(), Prior::Medium)).unwrap();
+
+        let mut app = App::new(&store).unwrap();
+        app.state.select_first();
+        app.browse(KeyCode::Char('e').into()).unwrap();
+        assert_eq!(app.input, "\\-draft the plan, \\+1 for \\--due dates and \\\\o/");
+        app.input.push_str(" +fitness");
+        app.type_key(KeyCode::Enter.into()).unwrap();
+
+        let todo = store.get(id).unwrap();
+        assert_eq!(todo.thing, thing);
+        assert_eq!(todo.tags, ["fitness"]);
+    }
+}
@@ -0,0 +1,76 @@
+// Fixtures shared by the integration tests.
+#![allow(dead_code)] // each test file uses only part of them
+
+use std::ops::Deref;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+use tempfile::TempDir;
+use zeroize::Zeroizing;
+
+use todo::{Prior, Todo, TodoError, TodoStore};
+
+/// A store in its own temporary directory, removed once the store is dropped.
+pub struct TestStore {
+    pub store: TodoStore,
+    pub dir: TempDir,
+}
+
+impl Deref for TestStore {
+    type Target = TodoStore;
+
+    fn deref(&self) -> &TodoStore {
+        &self.store
+    }
+}
+
+pub fn store() -> TestStore {
+    let dir = tempfile::tempdir().unwrap();
+    TestStore {
+        store: TodoStore::open(dir.path()).unwrap(),
+        dir,
+    }
+}
+
+/// Adds a medium priority todo and returns its ID.
+pub fn add(store: &TodoStore, thing: &str) -> u64 {
+    add_with(store, thing, |_| {})
+}
+
+/// Adds a medium priority todo after `fill` has set the rest of it.
+pub fn add_with(store: &TodoStore, thing: &str, fill: impl FnOnce(&mut Todo)) -> u64 {
+    let mut todo = Todo::new(thing.into(), Prior::Medium);
+    fill(&mut todo);
+    store.insert(&todo).unwrap()
+}
+
+/// Opens the store at `path` again. sled's flusher thread can hold the lock for a
+/// moment after the previous store is dropped, so a locked database is retried.
+pub fn reopen(path: &Path, passphrase: impl Fn() -> todo::Result<Zeroizing<String>>) -> todo::Result<TodoStore> {
+    for _ in 0..50 {
+        match TodoStore::open_with(path, &passphrase) {
+            Err(TodoError::Locked(_)) => thread::sleep(Duration::from_millis(20)),
+            other => return other,
+        }
+    }
+    TodoStore::open_with(path, passphrase)
+}
+
+/// The database at `path` without a store around it, to write records the way
+/// older versions did. Retried like [`reopen`].
+pub fn raw_db(path: &Path) -> sled::Db {
+    for _ in 0..50 {
+        match sled::open(path) {
+            Err(sled::Error::Io(e)) if e.to_string().contains("could not acquire lock") => {
+                thread::sleep(Duration::from_millis(20))
+            }
+            other => return other.unwrap(),
+        }
+    }
+    sled::open(path).unwrap()
+}
+
+/// For stores that are not expected to be encrypted.
+pub fn no_passphrase() -> todo::Result<Zeroizing<String>> {
+    Err(TodoError::Passphrase("none given".into()))
+}
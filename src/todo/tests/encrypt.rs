@@ -0,0 +1,40 @@
+// Encrypting a database, reopening it with the right and wrong passphrase, and rekeying.
+
+mod common;
+
+use zeroize::Zeroizing;
+
+use common::{no_passphrase, reopen, TestStore};
+use todo::{Prior, Todo, TodoError};
+
+fn passphrase(text: &'static str) -> impl Fn() -> todo::Result<Zeroizing<String>> {
+    move || Ok(Zeroizing::new(text.to_string()))
+}
+
+#[test]
+fn sealed_todos_need_the_passphrase() {
+    let TestStore { mut store, dir } = common::store();
+    let id = store.insert(&Todo::new("pin is 0000".into(), Prior::High)).unwrap();
+    store.set_passphrase(Some("first")).unwrap();
+    store.insert(&Todo::new("added while sealed".into(), Prior::Medium)).unwrap();
+    drop(store);
+
+    assert!(matches!(reopen(dir.path(), no_passphrase), Err(TodoError::Passphrase(_))));
+    assert!(matches!(reopen(dir.path(), passphrase("wrong")), Err(TodoError::Passphrase(_))));
+
+    let mut store = reopen(dir.path(), passphrase("first")).unwrap();
+    assert!(store.is_encrypted());
+    assert_eq!(store.get(id).unwrap().thing, "pin is 0000");
+    assert_eq!(store.iter().count(), 2);
+    assert!(store.undo().unwrap().is_some(), "the event log reads back too");
+
+    store.set_passphrase(Some("second")).unwrap();
+    drop(store);
+    assert!(matches!(reopen(dir.path(), passphrase("first")), Err(TodoError::Passphrase(_))));
+    let mut store = reopen(dir.path(), passphrase("second")).unwrap();
+    assert_eq!(store.get(id).unwrap().thing, "pin is 0000");
+
+    store.set_passphrase(None).unwrap();
+    drop(store);
+    assert_eq!(reopen(dir.path(), no_passphrase).unwrap().get(id).unwrap().thing, "pin is 0000");
+}
@@ -0,0 +1,99 @@
+// Undo and redo over the event log.
+
+mod common;
+
+use bincode::config::standard;
+use bincode::Encode;
+
+use common::{add, no_passphrase, raw_db, reopen, TestStore};
+use todo::history::{EventKind, EVENT_LIMIT};
+use todo::{Prior, TodoError, TodoStore};
+
+fn things(store: &TodoStore) -> Vec<String> {
+    store.iter().map(|item| item.unwrap().1.thing).collect()
+}
+
+#[test]
+fn removing_several_todos_is_undone_at_once() {
+    let store = common::store();
+    for thing in ["a", "b", "c"] {
+        add(&store, thing);
+    }
+    store.transaction(|tx| [1, 3].iter().try_for_each(|&id| tx.delete(id).map(|_| ()))).unwrap();
+    assert_eq!(things(&store), ["b"]);
+
+    let undone = store.undo().unwrap().unwrap();
+    assert_eq!(undone.changes.len(), 2);
+    assert_eq!(things(&store), ["a", "b", "c"]);
+    assert_eq!(store.history().next().unwrap().unwrap().kind, EventKind::Undo(undone.seq));
+
+    store.redo().unwrap().unwrap();
+    assert_eq!(things(&store), ["b"]);
+    assert!(store.redo().unwrap().is_none(), "nothing left to redo");
+    store.undo().unwrap().unwrap();
+    assert_eq!(things(&store), ["a", "b", "c"]);
+}
+
+#[test]
+fn a_new_change_clears_redo() {
+    let store = common::store();
+    let id = add(&store, "draft");
+    let mut todo = store.get(id).unwrap();
+    todo.thing = "final".into();
+    store.update(id, &todo).unwrap();
+
+    store.undo().unwrap();
+    assert_eq!(store.get(id).unwrap().thing, "draft");
+    add(&store, "other");
+    assert!(store.redo().unwrap().is_none());
+
+    // Undo walks back past the new change to the edit and the add
+    store.undo().unwrap();
+    store.undo().unwrap();
+    store.undo().unwrap();
+    assert!(things(&store).is_empty());
+    assert!(store.undo().unwrap().is_none());
+}
+
+// The first record layout, which the store still reads
+#[derive(Encode)]
+struct Original {
+    thing: String,
+    priority: Prior,
+    done: bool,
+}
+
+#[test]
+fn todos_changed_outside_the_log_are_not_replayed() {
+    let TestStore { store, dir } = common::store();
+    let id = add(&store, "draft");
+    drop(store);
+
+    // Rewrite the record behind the store's back
+    let db = raw_db(dir.path());
+    let record = Original { thing: "edited elsewhere".into(), priority: Prior::High, done: false };
+    let bytes = bincode::encode_to_vec(record, standard()).unwrap();
+    db.open_tree("todos").unwrap().insert(id.to_be_bytes(), bytes).unwrap();
+    db.flush().unwrap();
+    drop(db);
+
+    let store = reopen(dir.path(), no_passphrase).unwrap();
+    assert!(matches!(store.undo(), Err(TodoError::InvalidInput(_))));
+    assert_eq!(store.get(id).unwrap().thing, "edited elsewhere", "a refused undo changes nothing");
+}
+
+#[test]
+fn the_log_keeps_the_latest_events() {
+    let store = common::store();
+    for n in 0..EVENT_LIMIT + 2 {
+        add(&store, &format!("todo {}", n));
+    }
+
+    let kept: Vec<u64> = store.history().map(|event| event.unwrap().seq).collect();
+    assert_eq!(kept.len() as u64, EVENT_LIMIT);
+    assert_eq!(kept.last(), Some(&3), "events 1 and 2 were dropped");
+
+    // Undoing as deep as the undo stack goes still finds every event
+    while store.undo().unwrap().is_some() {}
+    assert_eq!(store.history().count() as u64, EVENT_LIMIT);
+}
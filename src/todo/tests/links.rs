@@ -0,0 +1,60 @@
+// Subtasks and dependencies: links have to point at existing todos and may not loop.
+
+mod common;
+
+use common::{add, add_with};
+use todo::{TodoError, TodoStore};
+
+fn set_parent(store: &TodoStore, id: u64, parent: u64) -> todo::Result<()> {
+    let mut todo = store.get(id)?;
+    todo.parent = Some(parent);
+    store.update(id, &todo)
+}
+
+fn wait_on(store: &TodoStore, id: u64, blocker: u64) -> todo::Result<()> {
+    let mut todo = store.get(id)?;
+    todo.blocked_by.push(blocker);
+    store.update(id, &todo)
+}
+
+#[test]
+fn parents_cannot_loop() {
+    let store = common::store();
+    let a = add(&store, "move house");
+    let b = add_with(&store, "pack books", |todo| todo.parent = Some(a));
+
+    assert!(matches!(set_parent(&store, a, a), Err(TodoError::InvalidInput(_))));
+    assert!(matches!(set_parent(&store, a, b), Err(TodoError::InvalidInput(_))));
+    assert_eq!(store.get(a).unwrap().parent, None, "a refused write changes nothing");
+    assert_eq!(store.children(a).unwrap(), [(b, store.get(b).unwrap())]);
+}
+
+#[test]
+fn dependencies_cannot_loop() {
+    let store = common::store();
+    let a = add(&store, "buy paint");
+    let b = add_with(&store, "paint the hall", |todo| todo.blocked_by = vec![a]);
+    let c = add_with(&store, "hang pictures", |todo| todo.blocked_by = vec![b]);
+
+    assert!(matches!(wait_on(&store, a, a), Err(TodoError::InvalidInput(_))));
+    assert!(matches!(wait_on(&store, a, b), Err(TodoError::InvalidInput(_))));
+    assert!(matches!(wait_on(&store, a, c), Err(TodoError::InvalidInput(_))), "longer loops too");
+    assert_eq!(store.open_blockers(&store.get(c).unwrap()).unwrap(), [b]);
+}
+
+#[test]
+fn links_need_an_existing_todo() {
+    let store = common::store();
+    let a = add(&store, "file taxes");
+
+    assert!(matches!(set_parent(&store, a, 99), Err(TodoError::NotFound(99))));
+    assert!(matches!(wait_on(&store, a, 99), Err(TodoError::NotFound(99))));
+
+    // Links made before their target was removed stay, and read as done
+    let b = add_with(&store, "find receipts", |todo| todo.blocked_by = vec![a]);
+    store.delete(a).unwrap();
+    assert!(store.open_blockers(&store.get(b).unwrap()).unwrap().is_empty());
+    let mut todo = store.get(b).unwrap();
+    todo.thing = "find all receipts".into();
+    store.update(b, &todo).unwrap();
+}
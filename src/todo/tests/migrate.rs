@@ -0,0 +1,92 @@
+// Records written by older versions, before and after records had a header,
+// read back and rewritten by `migrate`.
+
+mod common;
+
+use bincode::config::standard;
+use bincode::Encode;
+
+use common::{no_passphrase, raw_db, reopen};
+use todo::query::{Query, Term};
+use todo::{Prior, TodoStore};
+
+// `Todo` as the first versions wrote it, with no header
+#[derive(Encode)]
+struct Original {
+    thing: String,
+    priority: Prior,
+    done: bool,
+}
+
+// After due dates and timestamps were added
+#[derive(Encode)]
+struct Dated {
+    thing: String,
+    priority: Prior,
+    done: bool,
+    due: Option<i64>,
+    created: i64,
+    completed: Option<i64>,
+}
+
+// After tags and projects were added
+#[derive(Encode)]
+struct Tagged {
+    thing: String,
+    priority: Prior,
+    done: bool,
+    due: Option<i64>,
+    created: i64,
+    completed: Option<i64>,
+    tags: Vec<String>,
+    project: Option<String>,
+}
+
+fn encode(record: impl Encode) -> Vec<u8> {
+    bincode::encode_to_vec(record, standard()).unwrap()
+}
+
+#[test]
+fn old_records_are_read_and_rewritten() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = raw_db(dir.path());
+    let todos = db.open_tree("todos").unwrap();
+    let records = [
+        encode(Original { thing: "water plants".into(), priority: Prior::Soft, done: true }),
+        encode(Dated {
+            thing: "pay rent".into(),
+            priority: Prior::High,
+            done: false,
+            due: Some(1_700_000_000),
+            created: 1_690_000_000,
+            completed: None,
+        }),
+        encode(Tagged {
+            thing: "book flights".into(),
+            priority: Prior::Medium,
+            done: false,
+            due: None,
+            created: 1_690_000_000,
+            completed: None,
+            tags: vec!["travel".into()],
+            project: Some("holiday".into()),
+        }),
+    ];
+    for (id, record) in (1u64..).zip(records) {
+        todos.insert(id.to_be_bytes(), record).unwrap();
+    }
+    db.flush().unwrap();
+    drop((todos, db));
+
+    let store: TodoStore = reopen(dir.path(), no_passphrase).unwrap();
+    let plants = store.get(1).unwrap();
+    assert_eq!((plants.thing.as_str(), plants.priority, plants.done), ("water plants", Prior::Soft, true));
+    assert_eq!(store.get(2).unwrap().due, Some(1_700_000_000));
+    let tagged = Query { terms: vec![Term::Tag("travel".into())] };
+    assert_eq!(store.query(&tagged).unwrap(), [(3, store.get(3).unwrap())], "the indexes are built from them");
+
+    let before: Vec<_> = store.iter().map(Result::unwrap).collect();
+    assert_eq!(store.migrate().unwrap(), 3);
+    assert_eq!(store.migrate().unwrap(), 0, "every record is current now");
+    assert_eq!(store.iter().map(Result::unwrap).collect::<Vec<_>>(), before);
+}
@@ -0,0 +1,47 @@
+// Completing a recurring todo adds its next occurrence, and reopening takes it back.
+
+mod common;
+
+use common::add_with;
+use todo::{Recurrence, TodoError, TodoStore};
+
+fn reopen(store: &TodoStore, id: u64) -> todo::Result<()> {
+    store.transaction(|tx| tx.reopen(id))
+}
+
+#[test]
+fn reopening_takes_back_the_next_occurrence() {
+    let store = common::store();
+    let id = add_with(&store, "water plants", |todo| {
+        todo.due = Some(1_700_000_000);
+        todo.recur = Some(Recurrence::Daily);
+    });
+    let next = store.transaction(|tx| tx.complete(id)).unwrap().unwrap();
+    assert_eq!(store.get(id).unwrap().recur, None);
+    assert_eq!(store.get(next).unwrap().recur, Some(Recurrence::Daily));
+
+    reopen(&store, id).unwrap();
+    let todo = store.get(id).unwrap();
+    assert!(!todo.done);
+    assert_eq!(todo.recur, Some(Recurrence::Daily));
+    assert!(matches!(store.get(next), Err(TodoError::NotFound(_))));
+
+    // Undoing the reopen brings the occurrence back, and reopening takes it again
+    store.undo().unwrap();
+    assert!(store.get(next).is_ok());
+    reopen(&store, id).unwrap();
+    assert_eq!(store.iter().count(), 1);
+}
+
+#[test]
+fn a_todo_whose_next_occurrence_is_done_stays_done() {
+    let store = common::store();
+    let id = add_with(&store, "pay rent", |todo| todo.recur = Some(Recurrence::Monthly(1)));
+    let next = store.transaction(|tx| tx.complete(id)).unwrap().unwrap();
+    store.transaction(|tx| tx.complete(next)).unwrap();
+
+    assert!(matches!(reopen(&store, id), Err(TodoError::InvalidInput(_))));
+    assert!(store.get(id).unwrap().done);
+    reopen(&store, next).unwrap();
+    assert_eq!(store.iter().filter(|item| !item.as_ref().unwrap().1.done).count(), 1);
+}
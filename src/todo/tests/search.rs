@@ -0,0 +1,46 @@
+// Ranking, prefix and typo matching, and the word index following edits.
+
+mod common;
+
+use common::{add, add_with};
+use todo::TodoStore;
+
+fn add_noted(store: &TodoStore, thing: &str, notes: &str) -> u64 {
+    add_with(store, thing, |todo| todo.notes = Some(notes.into()))
+}
+
+fn ids(store: &TodoStore, query: &str) -> Vec<u64> {
+    store.search(query).unwrap().into_iter().map(|(id, _)| id).collect()
+}
+
+#[test]
+fn whole_words_beat_prefixes_and_typos() {
+    let store = common::store();
+    let typo = add(&store, "Sprint planning");
+    let prefix = add(&store, "Print labels");
+    let whole = add_noted(&store, "Print boarding passes", "print them in colour");
+    let notes_only = add_noted(&store, "Airport run", "print the tickets");
+    add(&store, "Water plants");
+
+    assert_eq!(ids(&store, "PRINT"), [whole, prefix, notes_only, typo]);
+    assert_eq!(ids(&store, "sprnt"), [typo]);
+    assert_eq!(ids(&store, "print tickets"), [notes_only]);
+    assert!(ids(&store, "print plants").is_empty(), "every word has to match");
+}
+
+#[test]
+fn the_index_follows_edits_and_removals() {
+    let store = common::store();
+    let id = add(&store, "Book flights");
+
+    let mut todo = store.get(id).unwrap();
+    todo.thing = "Book trains".into();
+    store.update(id, &todo).unwrap();
+    assert!(ids(&store, "flights").is_empty());
+    assert_eq!(ids(&store, "trains"), [id]);
+
+    store.delete(id).unwrap();
+    assert!(ids(&store, "trains").is_empty());
+    store.undo().unwrap();
+    assert_eq!(ids(&store, "book"), [id]);
+}
@@ -0,0 +1,190 @@
+// Two devices syncing through a server on localhost, each with its own database.
+
+mod common;
+
+use std::thread;
+
+use common::{add, add_with, TestStore};
+use todo::sync::{self, Server, Synced};
+use todo::{Prior, Todo, TodoStore};
+
+// Starts a server on a free port for the rest of the test run and returns its URL
+fn server() -> String {
+    let server = Server::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", server.addr());
+    let TestStore { store, dir } = common::store();
+    thread::spawn(move || {
+        let _dir = dir; // removed only when the test process ends
+        server.run(&store)
+    });
+    url
+}
+
+fn find(store: &TodoStore, thing: &str) -> (u64, Todo) {
+    store
+        .iter()
+        .map(Result::unwrap)
+        .find(|(_, todo)| todo.thing == thing)
+        .unwrap_or_else(|| panic!("no todo '{}'", thing))
+}
+
+fn things(store: &TodoStore) -> Vec<String> {
+    let mut things: Vec<String> = store.iter().map(|item| item.unwrap().1.thing).collect();
+    things.sort();
+    things
+}
+
+fn sync(store: &TodoStore, url: &str) -> Synced {
+    sync::sync(store, url).unwrap()
+}
+
+#[test]
+fn todos_reach_the_other_device_with_their_links() {
+    let url = server();
+    let (a, b) = (common::store(), common::store());
+    add(&b, "pad the ids");
+    let parent = add(&a, "move house");
+    add_with(&a, "pack books", |todo| {
+        todo.priority = Prior::High;
+        todo.parent = Some(parent);
+    });
+
+    assert_eq!(sync(&a, &url), Synced { sent: 2, received: 0 });
+    assert_eq!(sync(&b, &url), Synced { sent: 1, received: 2 });
+    assert_eq!(things(&b), ["move house", "pack books", "pad the ids"]);
+
+    let (parent, _) = find(&b, "move house");
+    let (_, child) = find(&b, "pack books");
+    assert_eq!(child.parent, Some(parent));
+    assert_eq!(child.priority, Prior::High);
+}
+
+#[test]
+fn syncing_again_sends_nothing() {
+    let url = server();
+    let (a, b) = (common::store(), common::store());
+    add(&a, "water plants");
+    sync(&a, &url);
+    sync(&b, &url);
+    sync(&a, &url);
+
+    assert_eq!(sync(&a, &url), Synced { sent: 0, received: 0 });
+    assert_eq!(sync(&b, &url), Synced { sent: 0, received: 0 });
+}
+
+#[test]
+fn edits_to_different_fields_both_survive() {
+    let url = server();
+    let (a, b) = (common::store(), common::store());
+    let id = add(&a, "call the bank");
+    sync(&a, &url);
+    sync(&b, &url);
+
+    let mut todo = a.get(id).unwrap();
+    todo.thing = "call the bank about the card".into();
+    a.update(id, &todo).unwrap();
+    let (id_b, mut todo) = find(&b, "call the bank");
+    todo.priority = Prior::Fuck;
+    todo.tags.push("money".into());
+    b.update(id_b, &todo).unwrap();
+
+    sync(&a, &url);
+    sync(&b, &url);
+    sync(&a, &url);
+    let merged = a.get(id).unwrap();
+    assert_eq!(merged.thing, "call the bank about the card");
+    assert_eq!(merged.priority, Prior::Fuck);
+    assert_eq!(merged.tags, ["money"]);
+    assert_eq!(b.get(id_b).unwrap(), merged);
+}
+
+#[test]
+fn the_later_edit_of_a_field_wins_everywhere() {
+    let url = server();
+    let (a, b) = (common::store(), common::store());
+    let id = add(&a, "draft");
+    sync(&a, &url);
+    sync(&b, &url);
+    let (id_b, _) = find(&b, "draft");
+
+    // Concurrent edits settle on the same value on both devices
+    a.update(id, &Todo::new("from a".into(), Prior::Medium)).unwrap();
+    b.update(id_b, &Todo::new("from b".into(), Prior::Medium)).unwrap();
+    sync(&a, &url);
+    sync(&b, &url);
+    sync(&a, &url);
+    let settled = a.get(id).unwrap().thing;
+    assert_eq!(b.get(id_b).unwrap().thing, settled);
+
+    // An edit made after seeing the other one always wins
+    b.update(id_b, &Todo::new("after both".into(), Prior::Medium)).unwrap();
+    sync(&b, &url);
+    sync(&a, &url);
+    assert_eq!(a.get(id).unwrap().thing, "after both");
+}
+
+#[test]
+fn removals_and_their_undo_spread() {
+    let url = server();
+    let (a, b) = (common::store(), common::store());
+    add(&a, "keep");
+    let id = add(&a, "drop");
+    sync(&a, &url);
+    sync(&b, &url);
+
+    a.delete(id).unwrap();
+    sync(&a, &url);
+    sync(&b, &url);
+    assert_eq!(things(&b), ["keep"]);
+
+    a.undo().unwrap();
+    sync(&a, &url);
+    sync(&b, &url);
+    assert_eq!(things(&b), ["drop", "keep"]);
+}
+
+#[test]
+fn a_loop_made_on_two_devices_is_broken() {
+    let url = server();
+    let (a, b) = (common::store(), common::store());
+    let first = add(&a, "first");
+    let second = add(&a, "second");
+    sync(&a, &url);
+    sync(&b, &url);
+
+    let mut todo = a.get(first).unwrap();
+    todo.blocked_by = vec![second];
+    a.update(first, &todo).unwrap();
+    let (first_b, _) = find(&b, "first");
+    let (second_b, mut todo) = find(&b, "second");
+    todo.blocked_by = vec![first_b];
+    b.update(second_b, &todo).unwrap();
+
+    sync(&a, &url);
+    sync(&b, &url);
+    sync(&a, &url);
+    let waits = |store: &TodoStore| {
+        let (_, first) = find(store, "first");
+        let (_, second) = find(store, "second");
+        (first.blocked_by.is_empty(), second.blocked_by.is_empty())
+    };
+    assert_ne!(waits(&a), (false, false), "both still wait on each other");
+    assert_eq!(waits(&a), waits(&b));
+}
+
+#[test]
+fn a_peer_on_another_protocol_is_told_so() {
+    let url = format!("{}/sync", server());
+    let response = ureq::get(&url).call().unwrap();
+    assert_eq!(response.header("X-Todo-Sync"), Some(sync::PROTOCOL.to_string().as_str()));
+
+    // A batch from before the protocol field, with one stamp too few
+    let old = r#"{"seen":{},"records":[{"uid":{"device":1,"id":1},"stamps":[]}]}"#;
+    match ureq::post(&url).send_string(old) {
+        Err(ureq::Error::Status(400, response)) => {
+            let message = response.into_string().unwrap();
+            assert!(message.contains("sync protocol 1 but this side speaks 2"), "{}", message);
+        }
+        other => panic!("old batch answered with {:?}", other.map(|r| r.status())),
+    }
+}
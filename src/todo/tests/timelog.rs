@@ -0,0 +1,79 @@
+// Starting and stopping the timer, and reports cutting intervals at the range.
+
+mod common;
+
+use common::{add, add_with};
+use todo::timelog::{self, Interval};
+use todo::TodoError;
+
+const HOUR: i64 = 3600;
+
+#[test]
+fn only_one_timer_runs_at_a_time() {
+    let store = common::store();
+    let first = add(&store, "Write report");
+    let second = add(&store, "Review report");
+
+    store.start(first, 100).unwrap();
+    assert!(matches!(store.start(second, 200), Err(TodoError::InvalidInput(_))));
+    assert!(matches!(store.start(first, 200), Err(TodoError::InvalidInput(_))));
+    assert_eq!(store.running().unwrap(), Some(Interval { id: first, start: 100, end: None }));
+
+    let stopped = store.stop(400).unwrap();
+    assert_eq!(stopped, Some(Interval { id: first, start: 100, end: Some(400) }));
+    assert_eq!(store.stop(500).unwrap(), None);
+    store.start(second, 500).unwrap();
+    store.stop(600).unwrap();
+    assert!(matches!(store.start(99, 700), Err(TodoError::NotFound(99))));
+}
+
+#[test]
+fn reports_add_up_time_inside_the_range() {
+    let store = common::store();
+    let both = add_with(&store, "Fix login", |todo| {
+        todo.tags = vec!["work".into(), "bug".into()];
+        todo.project = Some("web".into());
+    });
+    let work = add_with(&store, "Plan sprint", |todo| todo.tags = vec!["work".into()]);
+
+    store.start(both, 0).unwrap();
+    store.stop(2 * HOUR).unwrap(); // half before the range
+    store.start(work, 3 * HOUR).unwrap();
+    store.stop(4 * HOUR).unwrap();
+    store.start(both, 10 * HOUR).unwrap(); // after the range
+    store.stop(11 * HOUR).unwrap();
+    store.start(work, 5 * HOUR + 1800).unwrap(); // running, counted up to now
+
+    let report = timelog::report(&store, HOUR, 8 * HOUR, 6 * HOUR).unwrap();
+    assert_eq!(report.tasks.into_iter().collect::<Vec<_>>(), [(both, HOUR), (work, HOUR + 1800)]);
+    assert_eq!(report.tags["work"], 2 * HOUR + 1800);
+    assert_eq!(report.tags["bug"], HOUR);
+    assert_eq!(report.projects.into_iter().collect::<Vec<_>>(), [("web".to_string(), HOUR)]);
+    assert_eq!(report.total, 2 * HOUR + 1800);
+}
+
+#[test]
+fn finishing_or_removing_the_timed_todo_stops_the_timer() {
+    let store = common::store();
+    let other = add(&store, "Other");
+    let done = add(&store, "Send invoice");
+    let removed = add(&store, "Call printer");
+
+    store.start(done, 100).unwrap();
+    store.transaction(|tx| tx.complete(other)).unwrap();
+    assert_eq!(store.running().unwrap().map(|running| running.id), Some(done));
+    store.transaction(|tx| tx.complete(done)).unwrap();
+    assert_eq!(store.running().unwrap(), None);
+
+    store.start(removed, 200).unwrap();
+    store.delete(removed).unwrap();
+    assert_eq!(store.running().unwrap(), None);
+
+    let added = add(&store, "Undone soon");
+    store.start(added, 300).unwrap();
+    store.undo().unwrap();
+    assert_eq!(store.running().unwrap(), None);
+    let logged = store.intervals(0, i64::MAX).unwrap();
+    assert_eq!(logged.iter().map(|interval| interval.id).collect::<Vec<_>>(), [done, removed, added]);
+    assert!(logged.iter().all(|interval| interval.end.is_some()));
+}
@@ -0,0 +1,82 @@
+// Export followed by parse gives the todos back in every format, and importing the
+// same file twice adds nothing the second time.
+
+mod common;
+
+use chrono::{Local, NaiveDateTime, TimeZone};
+
+use todo::transfer::{self, Format};
+use todo::{Prior, Recurrence, Todo};
+
+fn local(text: &str) -> i64 {
+    let naive = NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M").unwrap();
+    Local.from_local_datetime(&naive).earliest().unwrap().timestamp()
+}
+
+// A dated, tagged todo, a done subtask of it with a priority and a todo waiting on
+// that subtask, with times todo.txt can hold: whole days for creation and completion
+fn items() -> Vec<(u64, Todo)> {
+    let trip = Todo {
+        due: Some(local("2026-03-12 09:30")),
+        created: local("2026-03-01 00:00"),
+        tags: vec!["admin".into(), "urgent".into()],
+        project: Some("travel".into()),
+        recur: Some(Recurrence::Weekly(0b1001)),
+        ..Todo::new("Renew passport".into(), Prior::High)
+    };
+    let photos = Todo {
+        done: true,
+        created: local("2026-03-02 00:00"),
+        completed: Some(local("2026-03-03 00:00")),
+        parent: Some(4),
+        ..Todo::new("Get photos taken".into(), Prior::Soft)
+    };
+    let form = Todo {
+        due: Some(local("2026-03-10 23:59")),
+        created: local("2026-03-02 00:00"),
+        blocked_by: vec![7],
+        notes: Some("the long form, not the renewal one".into()),
+        ..Todo::new("Fill in the form".into(), Prior::Medium)
+    };
+    vec![(4, trip), (7, photos), (9, form)]
+}
+
+fn round_trip(format: Format) -> Vec<(Option<u64>, Todo)> {
+    let mut bytes = Vec::new();
+    transfer::export(&items(), format, &mut bytes).unwrap();
+    transfer::parse(format, &bytes[..]).unwrap()
+}
+
+#[test]
+fn json_and_csv_keep_every_field() {
+    let expected: Vec<(Option<u64>, Todo)> = items().into_iter().map(|(id, todo)| (Some(id), todo)).collect();
+    assert_eq!(round_trip(Format::Json), expected);
+    assert_eq!(round_trip(Format::Csv), expected);
+}
+
+#[test]
+fn todo_txt_keeps_everything_but_notes() {
+    let expected: Vec<(Option<u64>, Todo)> = items()
+        .into_iter()
+        .map(|(id, todo)| (Some(id), Todo { notes: None, ..todo }))
+        .collect();
+    assert_eq!(round_trip(Format::TodoTxt), expected);
+}
+
+#[test]
+fn importing_twice_adds_nothing() {
+    let store = common::store();
+    let mut bytes = Vec::new();
+    transfer::export(&items(), Format::TodoTxt, &mut bytes).unwrap();
+    let entries = transfer::parse(Format::TodoTxt, &bytes[..]).unwrap();
+
+    let first = transfer::import(&store, &entries).unwrap();
+    assert_eq!((first.added.len(), first.duplicates), (3, 0));
+    let [trip, photos, form] = first.added[..] else { panic!("{:?}", first.added) };
+    assert_eq!(store.get(photos).unwrap().parent, Some(trip), "links follow the new IDs");
+    assert_eq!(store.get(form).unwrap().blocked_by, [photos]);
+
+    let second = transfer::import(&store, &entries).unwrap();
+    assert_eq!((second.added.len(), second.duplicates), (0, 3));
+    assert_eq!(store.iter().count(), 3);
+}